@@ -0,0 +1,97 @@
+#![feature(test)]
+
+extern crate test;
+
+use futures::{executor, io::AsyncRead, TryStreamExt};
+use futures_codec::{FramedRead, JsonCodec};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+#[derive(Serialize, Deserialize)]
+struct Document {
+    items: Vec<Item>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Item {
+    id: u64,
+    name: String,
+    tags: Vec<String>,
+}
+
+// Hands out the input a few bytes at a time, like a slow socket would
+struct SmallReads<'a> {
+    input: &'a [u8],
+    chunk: usize,
+}
+
+impl AsyncRead for SmallReads<'_> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let n = self.chunk.min(buf.len()).min(self.input.len());
+        buf[..n].copy_from_slice(&self.input[..n]);
+        self.input = &self.input[n..];
+        Poll::Ready(Ok(n))
+    }
+}
+
+fn document(len: usize) -> Vec<u8> {
+    let item = |id| Item {
+        id,
+        name: format!("item \"{}\"", id),
+        tags: vec!["a".repeat(16), "b".repeat(32)],
+    };
+    let item_len = serde_json::to_vec(&item(0)).unwrap().len();
+    let doc = Document {
+        items: (0..(len / item_len) as u64 + 1).map(item).collect(),
+    };
+    serde_json::to_vec(&doc).unwrap()
+}
+
+fn huge_document(b: &mut test::Bencher, len: usize, chunk: usize) {
+    let data = document(len);
+    b.bytes = data.len() as u64;
+    b.iter(|| {
+        executor::block_on(async {
+            let read = SmallReads {
+                input: test::black_box(&data),
+                chunk,
+            };
+            let mut framed = FramedRead::new(read, JsonCodec::<(), Document>::new());
+
+            framed.try_next().await.unwrap().unwrap().items.len()
+        })
+    })
+}
+
+#[bench]
+fn small_documents(b: &mut test::Bencher) {
+    let data = document(256).repeat(64);
+    b.bytes = data.len() as u64;
+    b.iter(|| {
+        executor::block_on(async {
+            let read = SmallReads {
+                input: test::black_box(&data),
+                chunk: 8 << 10,
+            };
+            let framed = FramedRead::new(read, JsonCodec::<(), Document>::new());
+
+            framed.try_collect::<Vec<_>>().await.unwrap().len()
+        })
+    })
+}
+
+#[bench]
+fn huge_1mb_in_8kb_reads(b: &mut test::Bencher) {
+    huge_document(b, 1 << 20, 8 << 10);
+}
+
+#[bench]
+fn huge_4mb_in_8kb_reads(b: &mut test::Bencher) {
+    huge_document(b, 4 << 20, 8 << 10);
+}
@@ -2,8 +2,11 @@
 
 extern crate test;
 
-use futures::{executor, io::Cursor, TryStreamExt};
+use futures::{executor, io::AsyncRead, io::Cursor, TryStreamExt};
 use futures_codec::{FramedRead, LinesCodec};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
 #[bench]
 fn short(b: &mut test::Bencher) {
@@ -16,7 +19,7 @@ fn short(b: &mut test::Bencher) {
     b.iter(|| {
         executor::block_on(async {
             let read = Cursor::new(test::black_box(&data));
-            let mut framed = FramedRead::new(read, LinesCodec {});
+            let mut framed = FramedRead::new(read, LinesCodec::new());
 
             framed.try_next().await.unwrap();
             framed.try_next().await.unwrap();
@@ -36,7 +39,7 @@ fn medium(b: &mut test::Bencher) {
     b.iter(|| {
         executor::block_on(async {
             let read = Cursor::new(test::black_box(&data));
-            let mut framed = FramedRead::new(read, LinesCodec {});
+            let mut framed = FramedRead::new(read, LinesCodec::new());
 
             framed.try_next().await.unwrap();
             framed.try_next().await.unwrap();
@@ -56,7 +59,7 @@ fn long(b: &mut test::Bencher) {
     b.iter(|| {
         executor::block_on(async {
             let read = Cursor::new(test::black_box(&data));
-            let mut framed = FramedRead::new(read, LinesCodec {});
+            let mut framed = FramedRead::new(read, LinesCodec::new());
 
             framed.try_next().await.unwrap();
             framed.try_next().await.unwrap();
@@ -64,3 +67,49 @@ fn long(b: &mut test::Bencher) {
         })
     })
 }
+
+// Hands out the input a few bytes at a time, like a slow socket would
+struct SmallReads<'a> {
+    input: &'a [u8],
+    chunk: usize,
+}
+
+impl AsyncRead for SmallReads<'_> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let n = self.chunk.min(buf.len()).min(self.input.len());
+        buf[..n].copy_from_slice(&self.input[..n]);
+        self.input = &self.input[n..];
+        Poll::Ready(Ok(n))
+    }
+}
+
+fn huge_line(b: &mut test::Bencher, len: usize, chunk: usize) {
+    let mut data = vec![b'a'; len];
+    data.push(b'\n');
+    b.bytes = data.len() as u64;
+    b.iter(|| {
+        executor::block_on(async {
+            let read = SmallReads {
+                input: test::black_box(&data),
+                chunk,
+            };
+            let mut framed = FramedRead::new(read, LinesCodec::new());
+
+            framed.try_next().await.unwrap().unwrap().len()
+        })
+    })
+}
+
+#[bench]
+fn huge_1mb_in_1kb_reads(b: &mut test::Bencher) {
+    huge_line(b, 1 << 20, 1 << 10);
+}
+
+#[bench]
+fn huge_4mb_in_4kb_reads(b: &mut test::Bencher) {
+    huge_line(b, 4 << 20, 4 << 10);
+}
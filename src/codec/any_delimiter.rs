@@ -0,0 +1,432 @@
+use super::FrameTooLarge;
+use crate::{Decoder, Encoder};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use memchr::{memchr, memchr2, memchr3, memmem};
+use std::cmp::Reverse;
+use std::io::{Error, ErrorKind};
+use std::marker::PhantomData;
+
+/// A `Codec` implementation that splits up data on any of several delimiters.
+///
+/// Each delimiter can be one or more bytes long. When more than one matches
+/// at the same position, the longest one ends the frame. Encoded items are
+/// followed by a single sequence, which need not be one of the delimiters.
+///
+/// Frames are decoded as `Bytes` by codecs created with [`new`](#method.new)
+/// and as `String` by codecs created with [`new_string`](#method.new_string).
+///
+/// ```
+/// # futures::executor::block_on(async move {
+/// use futures::TryStreamExt;
+/// use futures_codec::{AnyDelimiterCodec, FramedRead};
+///
+/// let input = "one,two;three\n".as_bytes();
+/// let codec = AnyDelimiterCodec::new_string(&[",", ";", "\n"], "\n");
+/// let items: Vec<String> = FramedRead::new(input, codec).try_collect().await?;
+///
+/// assert_eq!(items, vec!["one", "two", "three"]);
+/// # Ok::<_, std::io::Error>(())
+/// # }).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct AnyDelimiterCodec<T = Bytes> {
+    searcher: Searcher,
+    sequence_writer: Vec<u8>,
+    keep_delimiter: bool,
+    max_frame_length: Option<usize>,
+    discard_oversized: bool,
+    // Skipping the rest of an oversized frame
+    discarding: bool,
+    // Bytes of the buffer already known not to start a delimiter
+    next_index: usize,
+    item: PhantomData<T>,
+}
+
+impl AnyDelimiterCodec<Bytes> {
+    /// Creates a new `AnyDelimiterCodec` decoding frames as `Bytes`
+    ///
+    /// # Panics
+    ///
+    /// Panics if no delimiter is given or if one of them is empty.
+    pub fn new<I>(delimiters: I, sequence_writer: impl AsRef<[u8]>) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        Self::with_delimiters(delimiters, sequence_writer)
+    }
+}
+
+impl AnyDelimiterCodec<String> {
+    /// Creates a new `AnyDelimiterCodec` decoding frames as UTF-8 `String`s
+    ///
+    /// # Panics
+    ///
+    /// Panics if no delimiter is given or if one of them is empty.
+    pub fn new_string<I>(delimiters: I, sequence_writer: impl AsRef<[u8]>) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        Self::with_delimiters(delimiters, sequence_writer)
+    }
+}
+
+impl<T> AnyDelimiterCodec<T> {
+    fn with_delimiters<I>(delimiters: I, sequence_writer: impl AsRef<[u8]>) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        let delimiters: Vec<Vec<u8>> = delimiters
+            .into_iter()
+            .map(|d| d.as_ref().to_vec())
+            .collect();
+        assert!(
+            !delimiters.is_empty() && delimiters.iter().all(|d| !d.is_empty()),
+            "delimiters must not be empty"
+        );
+
+        Self {
+            searcher: Searcher::new(delimiters),
+            sequence_writer: sequence_writer.as_ref().to_vec(),
+            keep_delimiter: false,
+            max_frame_length: None,
+            discard_oversized: false,
+            discarding: false,
+            next_index: 0,
+            item: PhantomData,
+        }
+    }
+
+    /// Sets whether decoded frames end with the delimiter that was found,
+    /// defaults to `false`
+    pub fn with_keep_delimiter(mut self, keep: bool) -> Self {
+        self.keep_delimiter = keep;
+        self
+    }
+
+    /// Sets the maximum length of a frame, excluding its delimiter
+    ///
+    /// Decoding a longer frame returns a [`FrameTooLarge`] error, as soon as
+    /// more bytes than this are buffered without a delimiter.
+    pub fn with_max_frame_length(mut self, max: usize) -> Self {
+        self.max_frame_length = Some(max);
+        self
+    }
+
+    /// Sets whether oversized frames are skipped up to the next delimiter
+    /// after returning the error, so decoding can carry on with the next frame
+    pub fn with_discard_oversized(mut self, discard: bool) -> Self {
+        self.discard_oversized = discard;
+        self
+    }
+
+    // The bytes at the end of the buffer that may start a delimiter
+    fn unscanned(&self, len: usize) -> usize {
+        len.saturating_sub(self.searcher.max_len - 1)
+    }
+
+    // Finds the first delimiter from `start`, unless more bytes could still
+    // turn it into a longer one
+    fn find(&self, src: &[u8], start: usize, eof: bool) -> Option<(usize, usize)> {
+        self.searcher
+            .find(src, start)
+            .filter(|&(pos, _)| eof || !self.searcher.may_grow(&src[pos..]))
+    }
+
+    fn decode_frame(&mut self, src: &mut BytesMut, eof: bool) -> Result<Option<BytesMut>, Error> {
+        if self.discarding {
+            match self.find(src, 0, eof) {
+                Some((pos, len)) => {
+                    src.advance(pos + len);
+                    self.discarding = false;
+                }
+                None => {
+                    src.advance(self.unscanned(src.len()));
+                    return Ok(None);
+                }
+            }
+        }
+
+        if self.next_index > src.len() {
+            self.next_index = 0;
+        }
+        match self.find(src, self.next_index, eof) {
+            Some((pos, len)) => {
+                self.next_index = 0;
+                if let Some(max) = self.max_frame_length {
+                    if pos > max {
+                        if self.discard_oversized {
+                            src.advance(pos + len);
+                        }
+                        return Err(FrameTooLarge { len: pos, max }.into());
+                    }
+                }
+
+                let mut frame = src.split_to(pos + len);
+                if !self.keep_delimiter {
+                    frame.truncate(pos);
+                }
+                Ok(Some(frame))
+            }
+            None => {
+                self.next_index = self.unscanned(src.len());
+                match self.max_frame_length {
+                    Some(max) if self.next_index > max => {
+                        let len = self.next_index;
+                        if self.discard_oversized {
+                            src.advance(len);
+                            self.discarding = true;
+                            self.next_index = 0;
+                        }
+                        Err(FrameTooLarge { len, max }.into())
+                    }
+                    _ => Ok(None),
+                }
+            }
+        }
+    }
+
+    fn decode_frame_eof(&mut self, src: &mut BytesMut) -> Result<Option<BytesMut>, Error> {
+        match self.decode_frame(src, true)? {
+            Some(frame) => Ok(Some(frame)),
+            None if src.is_empty() => Ok(None),
+            // The rest of an oversized frame, which no delimiter will end
+            None if self.discarding => {
+                src.clear();
+                Ok(None)
+            }
+            None => {
+                self.next_index = 0;
+                Ok(Some(src.split_to(src.len())))
+            }
+        }
+    }
+
+    fn encode_frame(&mut self, item: &[u8], dst: &mut BytesMut) {
+        dst.reserve(item.len() + self.sequence_writer.len());
+        dst.put(item);
+        dst.put(&self.sequence_writer[..]);
+    }
+}
+
+impl Encoder for AnyDelimiterCodec<Bytes> {
+    type Item = Bytes;
+    type Error = Error;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode_frame(&item, dst);
+        Ok(())
+    }
+}
+
+impl Encoder for AnyDelimiterCodec<String> {
+    type Item = String;
+    type Error = Error;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode_frame(item.as_bytes(), dst);
+        Ok(())
+    }
+}
+
+impl Decoder for AnyDelimiterCodec<Bytes> {
+    type Item = Bytes;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(self.decode_frame(src, false)?.map(BytesMut::freeze))
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(self.decode_frame_eof(src)?.map(BytesMut::freeze))
+    }
+}
+
+impl Decoder for AnyDelimiterCodec<String> {
+    type Item = String;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.decode_frame(src, false)?.map(to_string).transpose()
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.decode_frame_eof(src)?.map(to_string).transpose()
+    }
+}
+
+fn to_string(buf: BytesMut) -> Result<String, Error> {
+    String::from_utf8(buf.to_vec()).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+/// Finds the earliest delimiter in a buffer, using the fastest search the
+/// set of delimiters allows.
+#[derive(Debug, Clone)]
+struct Searcher {
+    kind: SearchKind,
+    delimiters: Vec<Vec<u8>>,
+    max_len: usize,
+}
+
+#[derive(Debug, Clone)]
+enum SearchKind {
+    // Up to three single byte delimiters
+    Memchr(Vec<u8>),
+    // A single multi-byte delimiter
+    Memmem(Box<memmem::Finder<'static>>),
+    // Anything else: candidates are found from the first byte of each delimiter
+    FirstByte(Box<[bool; 256]>),
+}
+
+impl Searcher {
+    fn new(mut delimiters: Vec<Vec<u8>>) -> Self {
+        delimiters.sort();
+        delimiters.dedup();
+        // Try longer delimiters first so the longest match wins
+        delimiters.sort_by_key(|d| Reverse(d.len()));
+        let max_len = delimiters[0].len();
+
+        let kind = if max_len == 1 && delimiters.len() <= 3 {
+            SearchKind::Memchr(delimiters.iter().map(|d| d[0]).collect())
+        } else if delimiters.len() == 1 {
+            SearchKind::Memmem(Box::new(memmem::Finder::new(&delimiters[0]).into_owned()))
+        } else {
+            let mut first = Box::new([false; 256]);
+            for delimiter in &delimiters {
+                first[delimiter[0] as usize] = true;
+            }
+            SearchKind::FirstByte(first)
+        };
+
+        Self {
+            kind,
+            delimiters,
+            max_len,
+        }
+    }
+
+    // Returns the position and length of the first delimiter from `start`
+    fn find(&self, buf: &[u8], start: usize) -> Option<(usize, usize)> {
+        let haystack = &buf[start..];
+        match &self.kind {
+            SearchKind::Memchr(bytes) => {
+                let pos = match bytes[..] {
+                    [a] => memchr(a, haystack),
+                    [a, b] => memchr2(a, b, haystack),
+                    [a, b, c] => memchr3(a, b, c, haystack),
+                    _ => unreachable!(),
+                };
+                pos.map(|pos| (start + pos, 1))
+            }
+            SearchKind::Memmem(finder) => {
+                finder.find(haystack).map(|pos| (start + pos, self.max_len))
+            }
+            SearchKind::FirstByte(first) => {
+                for (i, b) in haystack.iter().enumerate() {
+                    if !first[*b as usize] {
+                        continue;
+                    }
+                    let rest = &haystack[i..];
+                    if let Some(d) = self.delimiters.iter().find(|d| rest.starts_with(d)) {
+                        return Some((start + i, d.len()));
+                    }
+                }
+                None
+            }
+        }
+    }
+
+    // Whether `rest`, starting with a delimiter, is also the start of a
+    // longer one that isn't all buffered yet
+    fn may_grow(&self, rest: &[u8]) -> bool {
+        rest.len() < self.max_len
+            && self
+                .delimiters
+                .iter()
+                .any(|d| d.len() > rest.len() && d.starts_with(rest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_prefers_the_longest_delimiter() {
+        let mut codec = AnyDelimiterCodec::new(&["\r", "\r\n", "\n"], "\r\n");
+        let mut src = BytesMut::from("a\r\nb\rc\n");
+
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(Bytes::from("a")));
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(Bytes::from("b")));
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(Bytes::from("c")));
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn it_prefers_the_longest_delimiter_across_reads() {
+        let mut codec = AnyDelimiterCodec::new(&["\r", "\r\n", "\n"], "\r\n");
+        let mut src = BytesMut::from("a\r");
+
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+        src.extend_from_slice(b"\nb\n");
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(Bytes::from("a")));
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(Bytes::from("b")));
+        assert!(src.is_empty());
+
+        // At the end of the input, the shorter delimiter is all there is
+        src.extend_from_slice(b"c\r");
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+        assert_eq!(codec.decode_eof(&mut src).unwrap(), Some(Bytes::from("c")));
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn it_finds_delimiters_split_across_reads() {
+        let mut codec = AnyDelimiterCodec::new(&["\r\n\r\n"], "\r\n\r\n").with_keep_delimiter(true);
+        let mut src = BytesMut::from("head\r\n");
+
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+        src.extend_from_slice(b"\r\nbody");
+        assert_eq!(
+            codec.decode(&mut src).unwrap(),
+            Some(Bytes::from("head\r\n\r\n"))
+        );
+        assert_eq!(&src[..], b"body");
+    }
+
+    #[test]
+    fn it_discards_oversized_frames() {
+        let mut codec = AnyDelimiterCodec::new(&[b"\0"], b"\0")
+            .with_max_frame_length(3)
+            .with_discard_oversized(true);
+        let mut src = BytesMut::from(&b"abcdef"[..]);
+
+        assert!(codec.decode(&mut src).is_err());
+        src.extend_from_slice(b"gh\0ok\0");
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(Bytes::from("ok")));
+    }
+
+    #[test]
+    fn it_discards_oversized_frames_at_eof() {
+        let mut codec = AnyDelimiterCodec::new(&["\r\n"], "\r\n")
+            .with_max_frame_length(3)
+            .with_discard_oversized(true);
+        let mut src = BytesMut::from("abcdef\r");
+
+        assert!(codec.decode_eof(&mut src).is_err());
+        assert_eq!(codec.decode_eof(&mut src).unwrap(), None);
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn it_encodes_the_sequence_writer() {
+        let mut codec = AnyDelimiterCodec::new_string(&[",", ";"], ";");
+        let mut dst = BytesMut::new();
+
+        codec.encode("a".to_owned(), &mut dst).unwrap();
+        codec.encode("b".to_owned(), &mut dst).unwrap();
+        assert_eq!(&dst[..], b"a;b;");
+    }
+}
@@ -0,0 +1,351 @@
+use std::io::Error as IoError;
+use std::marker::PhantomData;
+
+use super::varint::encode_varint;
+use super::{FrameTooLarge, VarintLengthCodec};
+use crate::{Decoder, Encoder};
+use bytes::buf::BufMutExt;
+use bytes::BytesMut;
+
+use bincode::{DefaultOptions, Options};
+use serde::{Deserialize, Serialize};
+
+/// A codec for bincode encoding and decoding using the bincode crate
+/// Enc is the type to encode, Dec is the type to decode
+///
+/// Bincode values don't carry their own length, so each one is prefixed
+/// with its length as an unsigned LEB128 varint, the same framing as
+/// [`VarintLengthCodec`](struct.VarintLengthCodec.html).
+///
+/// Values are encoded with `bincode::DefaultOptions` unless other options
+/// are passed to [`with_options`](#method.with_options), which is how the
+/// endianness, integer encoding and byte limit are configured.
+/// ```
+/// # use futures::{executor, SinkExt, TryStreamExt};
+/// # use futures::io::Cursor;
+/// use bincode::Options;
+/// use serde::{Serialize, Deserialize};
+/// use futures_codec::{BincodeCodec, Framed};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Something {
+///     pub data: u16,
+/// }
+///
+/// async move {
+///     # let mut buf = vec![];
+///     # let stream = Cursor::new(&mut buf);
+///     // let stream = ...
+///     let options = bincode::DefaultOptions::new()
+///         .with_big_endian()
+///         .with_fixint_encoding()
+///         .with_limit(1024);
+///     let codec = BincodeCodec::<Something, Something>::new().with_options(options);
+///     let mut framed = Framed::new(stream, codec);
+///
+///     while let Some(s) = framed.try_next().await.unwrap() {
+///         println!("{:?}", s.data);
+///     }
+/// };
+/// ```
+pub struct BincodeCodec<Enc, Dec, O = DefaultOptions> {
+    enc: PhantomData<Enc>,
+    dec: PhantomData<Dec>,
+    options: O,
+    framing: VarintLengthCodec,
+    max_frame_length: Option<usize>,
+}
+
+/// Bincode Codec error enumeration
+#[derive(Debug)]
+pub enum BincodeCodecError {
+    /// IO error
+    Io(IoError),
+    /// Bincode error, including values exceeding the configured byte limit
+    Bincode(bincode::Error),
+    /// A frame exceeded the maximum frame length
+    FrameTooLarge(FrameTooLarge),
+}
+
+impl std::fmt::Display for BincodeCodecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BincodeCodecError::Io(e) => write!(f, "I/O error: {}", e),
+            BincodeCodecError::Bincode(e) => write!(f, "Bincode error: {}", e),
+            BincodeCodecError::FrameTooLarge(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for BincodeCodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BincodeCodecError::Io(ref e) => Some(e),
+            BincodeCodecError::Bincode(ref e) => Some(e),
+            BincodeCodecError::FrameTooLarge(ref e) => Some(e),
+        }
+    }
+}
+
+impl From<IoError> for BincodeCodecError {
+    fn from(e: IoError) -> BincodeCodecError {
+        BincodeCodecError::Io(e)
+    }
+}
+
+impl From<bincode::Error> for BincodeCodecError {
+    fn from(e: bincode::Error) -> BincodeCodecError {
+        BincodeCodecError::Bincode(e)
+    }
+}
+
+impl From<FrameTooLarge> for BincodeCodecError {
+    fn from(e: FrameTooLarge) -> BincodeCodecError {
+        BincodeCodecError::FrameTooLarge(e)
+    }
+}
+
+impl<Enc, Dec> BincodeCodec<Enc, Dec>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+{
+    /// Creates a new `BincodeCodec` with the associated types, using
+    /// `bincode::DefaultOptions`
+    pub fn new() -> BincodeCodec<Enc, Dec> {
+        BincodeCodec {
+            enc: PhantomData,
+            dec: PhantomData,
+            options: DefaultOptions::new(),
+            framing: VarintLengthCodec::new(),
+            max_frame_length: None,
+        }
+    }
+}
+
+impl<Enc, Dec, O> BincodeCodec<Enc, Dec, O>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+    O: Options + Copy,
+{
+    /// Sets the bincode options used to encode and decode values
+    ///
+    /// Both ends of a connection must agree on these. A byte limit set with
+    /// `Options::with_limit` applies to each value rather than the stream.
+    pub fn with_options<P>(self, options: P) -> BincodeCodec<Enc, Dec, P>
+    where
+        P: Options + Copy,
+    {
+        BincodeCodec {
+            enc: PhantomData,
+            dec: PhantomData,
+            options,
+            framing: self.framing,
+            max_frame_length: self.max_frame_length,
+        }
+    }
+
+    /// Sets the maximum length of an encoded value, excluding its header
+    ///
+    /// Decoding a larger frame returns `BincodeCodecError::FrameTooLarge` as
+    /// soon as its header is read, as does encoding a larger value.
+    pub fn with_max_frame_length(mut self, max: usize) -> Self {
+        self.framing = self.framing.with_max_frame_length(max);
+        self.max_frame_length = Some(max);
+        self
+    }
+
+    /// Sets whether oversized frames are skipped after returning the error,
+    /// so decoding can carry on with the next frame
+    pub fn with_discard_oversized(mut self, discard: bool) -> Self {
+        self.framing = self.framing.with_discard_oversized(discard);
+        self
+    }
+}
+
+impl<Enc, Dec, O> std::fmt::Debug for BincodeCodec<Enc, Dec, O> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BincodeCodec")
+            .field("framing", &self.framing)
+            .finish()
+    }
+}
+
+impl<Enc, Dec, O> Clone for BincodeCodec<Enc, Dec, O>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+    O: Options + Copy,
+{
+    /// Clone creates a new instance of the `BincodeCodec` with the same configuration
+    fn clone(&self) -> BincodeCodec<Enc, Dec, O> {
+        BincodeCodec {
+            enc: PhantomData,
+            dec: PhantomData,
+            options: self.options,
+            framing: self.framing,
+            max_frame_length: self.max_frame_length,
+        }
+    }
+}
+
+fn framing_error(e: IoError) -> BincodeCodecError {
+    match e.get_ref().and_then(|e| e.downcast_ref::<FrameTooLarge>()) {
+        Some(too_large) => BincodeCodecError::FrameTooLarge(*too_large),
+        None => e.into(),
+    }
+}
+
+/// Decoder impl parses bincode values from length-prefixed frames
+impl<Enc, Dec, O> Decoder for BincodeCodec<Enc, Dec, O>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+    O: Options + Copy,
+{
+    type Item = Dec;
+    type Error = BincodeCodecError;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let frame = match self.framing.decode(buf).map_err(framing_error)? {
+            Some(frame) => frame,
+            None => return Ok(None),
+        };
+
+        // The whole frame is consumed even if it fails to deserialize
+        Ok(Some(self.options.deserialize(&frame)?))
+    }
+}
+
+/// Encoder impl encodes values to length-prefixed frames
+impl<Enc, Dec, O> Encoder for BincodeCodec<Enc, Dec, O>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+    O: Options + Copy,
+{
+    type Item = Enc;
+    type Error = BincodeCodecError;
+
+    fn encode(&mut self, data: Self::Item, buf: &mut BytesMut) -> Result<(), Self::Error> {
+        // Bincode can size a value up front, so it's written without a copy
+        let len = self.options.serialized_size(&data)?;
+        if let Some(max) = self.max_frame_length {
+            if len > max as u64 {
+                let len = len as usize;
+                return Err(FrameTooLarge { len, max }.into());
+            }
+        }
+
+        let start = buf.len();
+        encode_varint(len, buf);
+        buf.reserve(len as usize);
+        if let Err(e) = self.options.serialize_into((&mut *buf).writer(), &data) {
+            // Don't leave the header or part of the value in the buffer
+            buf.truncate(start);
+            return Err(e.into());
+        }
+
+        Ok(())
+    }
+}
+
+impl<Enc, Dec> Default for BincodeCodec<Enc, Dec>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bincode::Options;
+    use bytes::BytesMut;
+    use serde::{Deserialize, Serialize};
+
+    use super::{BincodeCodec, BincodeCodecError};
+    use crate::{Decoder, Encoder};
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct TestStruct {
+        pub name: String,
+        pub data: u16,
+    }
+
+    #[test]
+    fn bincode_codec_encode_decode() {
+        let mut codec = BincodeCodec::<TestStruct, TestStruct>::new();
+        let mut buff = BytesMut::new();
+
+        let item1 = TestStruct {
+            name: "Test name".to_owned(),
+            data: 16,
+        };
+        codec.encode(item1.clone(), &mut buff).unwrap();
+        codec.encode(item1.clone(), &mut buff).unwrap();
+
+        // Varint length, then varint string length, name and data
+        assert_eq!(&buff[..3], b"\x0b\x09T");
+
+        // Every prefix of the first frame is incomplete, not an error
+        let mut input = buff.split();
+        for _ in 0..11 {
+            buff.extend_from_slice(&input.split_to(1));
+            assert_eq!(codec.decode(&mut buff).unwrap(), None);
+        }
+        buff.extend_from_slice(&input);
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(item1.clone()));
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(item1));
+        assert_eq!(codec.decode(&mut buff).unwrap(), None);
+        assert_eq!(buff.len(), 0);
+    }
+
+    #[test]
+    fn bincode_codec_options() {
+        let options = bincode::DefaultOptions::new()
+            .with_big_endian()
+            .with_fixint_encoding();
+        let mut codec = BincodeCodec::<u32, u32>::new().with_options(options);
+        let mut buff = BytesMut::new();
+
+        codec.encode(0x0102_0304, &mut buff).unwrap();
+        assert_eq!(&buff[..], b"\x04\x01\x02\x03\x04");
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(0x0102_0304));
+
+        let mut codec = BincodeCodec::<String, String>::new()
+            .with_options(bincode::DefaultOptions::new().with_limit(4));
+        assert!(matches!(
+            codec.encode("hello".to_owned(), &mut buff),
+            Err(BincodeCodecError::Bincode(_))
+        ));
+        assert_eq!(buff.len(), 0);
+    }
+
+    #[test]
+    fn bincode_codec_errors() {
+        let mut codec = BincodeCodec::<bool, bool>::new()
+            .with_max_frame_length(2)
+            .with_discard_oversized(true);
+        let mut buff = BytesMut::from(&b"\x01\x02\x03abc\x01\x01"[..]);
+
+        assert!(matches!(
+            codec.decode(&mut buff),
+            Err(BincodeCodecError::Bincode(_))
+        ));
+        assert!(matches!(
+            codec.decode(&mut buff),
+            Err(BincodeCodecError::FrameTooLarge(_))
+        ));
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(true));
+
+        let mut codec = BincodeCodec::<String, String>::new().with_max_frame_length(2);
+        assert!(matches!(
+            codec.encode("abc".to_owned(), &mut buff),
+            Err(BincodeCodecError::FrameTooLarge(_))
+        ));
+    }
+}
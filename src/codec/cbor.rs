@@ -1,14 +1,28 @@
 use std::io::Error as IoError;
 use std::marker::PhantomData;
 
+use super::cbor_scanner::CborScanner;
+use super::limits::{LimitExceeded, Limits};
+use super::FrameTooLarge;
 use crate::{Decoder, Encoder};
-use bytes::{Buf, BufMut, BytesMut};
+use bytes::buf::BufMutExt;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 
+use serde::de::IgnoredAny;
 use serde::{Deserialize, Serialize};
+use serde_cbor::ser::IoWrite;
 use serde_cbor::Error as CborError;
 
 /// A codec for JSON encoding and decoding using serde_cbor
 /// Enc is the type to encode, Dec is the type to decode
+///
+/// Items are read back-to-back, as in a CBOR sequence (RFC 8742). Use
+/// `serde_cbor::Value` as `Dec` to decode items of any shape. Semantic tags
+/// are ignored, unless the `cbor-tags` feature is enabled: they are then
+/// kept as `Value::Tag`, and other types must use `serde_cbor::tags::Tagged`
+/// to accept tagged items. A leading self-describe tag is always skipped.
+/// To forward items without decoding them, see
+/// [`RawCborCodec`](struct.RawCborCodec.html).
 /// ```
 /// # use futures::{executor, SinkExt, TryStreamExt};
 /// # use futures::io::Cursor;
@@ -36,6 +50,9 @@ use serde_cbor::Error as CborError;
 pub struct CborCodec<Enc, Dec> {
     enc: PhantomData<Enc>,
     dec: PhantomData<Dec>,
+    framer: CborFramer,
+    packed: bool,
+    self_describe: bool,
 }
 
 /// JSON Codec error enumeration
@@ -45,6 +62,23 @@ pub enum CborCodecError {
     Io(IoError),
     /// JSON error
     Cbor(CborError),
+    /// An item exceeded the maximum frame length
+    FrameTooLarge(FrameTooLarge),
+    /// An item was nested deeper than the maximum depth
+    NestingTooDeep {
+        /// The maximum depth
+        max: usize,
+    },
+    /// A string was longer than the maximum string length
+    StringTooLong {
+        /// The maximum length, in bytes
+        max: usize,
+    },
+    /// An array or map held more items than the maximum collection length
+    CollectionTooLong {
+        /// The maximum number of items
+        max: usize,
+    },
 }
 
 impl std::fmt::Display for CborCodecError {
@@ -52,6 +86,16 @@ impl std::fmt::Display for CborCodecError {
         match self {
             CborCodecError::Io(e) => write!(f, "I/O error: {}", e),
             CborCodecError::Cbor(e) => write!(f, "CBOR error: {}", e),
+            CborCodecError::FrameTooLarge(e) => write!(f, "{}", e),
+            CborCodecError::NestingTooDeep { max } => {
+                write!(f, "item is nested deeper than {} levels", max)
+            }
+            CborCodecError::StringTooLong { max } => {
+                write!(f, "string is longer than {} bytes", max)
+            }
+            CborCodecError::CollectionTooLong { max } => {
+                write!(f, "collection has more than {} items", max)
+            }
         }
     }
 }
@@ -61,6 +105,10 @@ impl std::error::Error for CborCodecError {
         match self {
             CborCodecError::Io(ref e) => Some(e),
             CborCodecError::Cbor(ref e) => Some(e),
+            CborCodecError::FrameTooLarge(ref e) => Some(e),
+            CborCodecError::NestingTooDeep { .. }
+            | CborCodecError::StringTooLong { .. }
+            | CborCodecError::CollectionTooLong { .. } => None,
         }
     }
 }
@@ -77,6 +125,22 @@ impl From<CborError> for CborCodecError {
     }
 }
 
+impl From<FrameTooLarge> for CborCodecError {
+    fn from(e: FrameTooLarge) -> CborCodecError {
+        CborCodecError::FrameTooLarge(e)
+    }
+}
+
+impl From<LimitExceeded> for CborCodecError {
+    fn from(e: LimitExceeded) -> CborCodecError {
+        match e {
+            LimitExceeded::Depth(max) => CborCodecError::NestingTooDeep { max },
+            LimitExceeded::StringLength(max) => CborCodecError::StringTooLong { max },
+            LimitExceeded::CollectionLength(max) => CborCodecError::CollectionTooLong { max },
+        }
+    }
+}
+
 impl<Enc, Dec> CborCodec<Enc, Dec>
 where
     for<'de> Dec: Deserialize<'de> + 'static,
@@ -87,8 +151,70 @@ where
         CborCodec {
             enc: PhantomData,
             dec: PhantomData,
+            framer: CborFramer::default(),
+            packed: false,
+            self_describe: false,
         }
     }
+
+    /// Sets whether items are encoded in the packed format
+    ///
+    /// Field names and enum variant names are replaced by their indices,
+    /// which is more compact but ties the encoding to the declaration order
+    /// of the types.
+    pub fn with_packed(mut self, packed: bool) -> Self {
+        self.packed = packed;
+        self
+    }
+
+    /// Sets whether each encoded item is prefixed with the self-describe tag
+    ///
+    /// The tag (55799) marks the data as CBOR and is ignored when decoding.
+    pub fn with_self_describe(mut self, self_describe: bool) -> Self {
+        self.self_describe = self_describe;
+        self
+    }
+
+    /// Sets the maximum length of an encoded item
+    ///
+    /// Decoding a larger item returns `CborCodecError::FrameTooLarge`, as
+    /// soon as more bytes than this are buffered for an incomplete item.
+    pub fn with_max_frame_length(mut self, max: usize) -> Self {
+        self.framer.max_frame_length = Some(max);
+        self
+    }
+
+    /// Sets whether oversized items are skipped after returning the error,
+    /// so decoding can carry on with the next item
+    ///
+    /// Otherwise an oversized item is left in the buffer, and decoding
+    /// returns the same error again.
+    pub fn with_discard_oversized(mut self, discard: bool) -> Self {
+        self.framer.discard_oversized = discard;
+        self
+    }
+
+    /// Sets the maximum nesting depth of arrays, maps and tags
+    pub fn with_max_depth(mut self, max: usize) -> Self {
+        self.framer.limits.max_depth = Some(max);
+        self
+    }
+
+    /// Sets the maximum length of a byte or text string
+    pub fn with_max_string_length(mut self, max: usize) -> Self {
+        self.framer.limits.max_string_length = Some(max);
+        self
+    }
+
+    /// Sets the maximum number of items in an array or entries in a map
+    ///
+    /// Limits are checked before an item is deserialized, and declared
+    /// lengths as soon as they are read. An item exceeding a limit fails
+    /// with the matching error and is not consumed.
+    pub fn with_max_collection_length(mut self, max: usize) -> Self {
+        self.framer.limits.max_collection_length = Some(max);
+        self
+    }
 }
 
 impl<Enc, Dec> Clone for CborCodec<Enc, Dec>
@@ -96,9 +222,91 @@ where
     for<'de> Dec: Deserialize<'de> + 'static,
     for<'de> Enc: Serialize + 'static,
 {
-    /// Clone creates a new instance of the `CborCodec`
+    /// Clone creates a new instance of the `CborCodec` with the same configuration
     fn clone(&self) -> CborCodec<Enc, Dec> {
-        CborCodec::new()
+        CborCodec {
+            enc: PhantomData,
+            dec: PhantomData,
+            framer: self.framer.clone(),
+            packed: self.packed,
+            self_describe: self.self_describe,
+        }
+    }
+}
+
+// Tag 55799, marking data as CBOR
+const SELF_DESCRIBE_TAG: &[u8] = b"\xd9\xd9\xf7";
+
+/// Splits a byte stream into CBOR items, shared by the CBOR codecs
+#[derive(Debug, Clone, Default, PartialEq)]
+struct CborFramer {
+    max_frame_length: Option<usize>,
+    discard_oversized: bool,
+    // Skipping the rest of an oversized item
+    discarding: bool,
+    scanner: CborScanner,
+    limits: Limits,
+}
+
+impl CborFramer {
+    /// Returns the end offset of the item at the front of the buffer, once
+    /// it is complete and within the limits
+    fn next_item(&mut self, buf: &mut BytesMut) -> Result<Option<usize>, CborCodecError> {
+        // Skip what remains of an oversized item
+        if self.discarding {
+            match self.scanner.scan(buf) {
+                Some(end) => {
+                    buf.advance(end);
+                    self.discarding = false;
+                }
+                None => {
+                    let n = buf.len();
+                    buf.clear();
+                    self.scanner.consume(n);
+                    return Ok(None);
+                }
+            }
+        }
+
+        // Only decode once a whole item is buffered, the scanner picks up
+        // where it left off on the previous call
+        let end = match self.scanner.scan_limited(buf, &self.limits)? {
+            Some(end) => end,
+            None => {
+                if let Some(max) = self.max_frame_length {
+                    if buf.len() > max {
+                        let len = buf.len();
+                        return Err(self.too_large(buf, len, max));
+                    }
+                }
+                return Ok(None);
+            }
+        };
+
+        if let Some(max) = self.max_frame_length {
+            if end > max {
+                if self.discard_oversized {
+                    buf.advance(end);
+                }
+                return Err(FrameTooLarge { len: end, max }.into());
+            }
+        }
+        Ok(Some(end))
+    }
+
+    fn too_large(&mut self, buf: &mut BytesMut, len: usize, max: usize) -> CborCodecError {
+        if self.discard_oversized {
+            match self.scanner.scan(buf) {
+                Some(end) => buf.advance(end),
+                None => {
+                    let n = buf.len();
+                    buf.clear();
+                    self.scanner.consume(n);
+                    self.discarding = true;
+                }
+            }
+        }
+        FrameTooLarge { len, max }.into()
     }
 }
 
@@ -112,26 +320,28 @@ where
     type Error = CborCodecError;
 
     fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let end = match self.framer.next_item(buf)? {
+            Some(end) => end,
+            None => return Ok(None),
+        };
+
+        // The self-describe tag carries no meaning of its own
+        let mut start = 0;
+        while buf[start..end].starts_with(SELF_DESCRIBE_TAG) {
+            start += SELF_DESCRIBE_TAG.len();
+        }
+
         // Build deserializer
-        let mut de = serde_cbor::Deserializer::from_slice(&buf);
+        let mut de = serde_cbor::Deserializer::from_slice(&buf[start..end]);
 
         // Attempt deserialization
         let res: Result<Dec, _> = serde::de::Deserialize::deserialize(&mut de);
 
-        // If we ran out before parsing, return none and try again later
-        let res = match res {
-            Ok(v) => Ok(Some(v)),
-            Err(e) if e.is_eof() => Ok(None),
-            Err(e) => Err(e.into()),
-        };
-
-        // Update offset from iterator
-        let offset = de.byte_offset();
-
-        // Advance buffer
-        buf.advance(offset);
+        // Advance buffer past the whole item, even if deserialization stopped
+        // partway through it
+        buf.advance(end);
 
-        res
+        Ok(Some(res?))
     }
 }
 
@@ -145,12 +355,22 @@ where
     type Error = CborCodecError;
 
     fn encode(&mut self, data: Self::Item, buf: &mut BytesMut) -> Result<(), Self::Error> {
-        // Encode cbor
-        let j = serde_cbor::to_vec(&data)?;
-
-        // Write to buffer
-        buf.reserve(j.len());
-        buf.put_slice(&j);
+        // Encode cbor straight into the buffer
+        let start = buf.len();
+        let mut ser = serde_cbor::Serializer::new(IoWrite::new((&mut *buf).writer()));
+        if self.packed {
+            ser = ser.packed_format();
+        }
+        let res = if self.self_describe {
+            ser.self_describe()
+        } else {
+            Ok(())
+        };
+        if let Err(e) = res.and_then(|()| data.serialize(&mut ser)) {
+            // Don't leave part of the item in the buffer
+            buf.truncate(start);
+            return Err(e.into());
+        }
 
         Ok(())
     }
@@ -166,12 +386,121 @@ where
     }
 }
 
+/// A codec splitting a CBOR sequence into items without deserializing them
+///
+/// Each item is checked to be well-formed and then yielded as `Bytes`,
+/// semantic tags included. The encoder writes pre-serialized `Bytes` as they
+/// are, which allows inspecting or forwarding CBOR without static types.
+///
+/// The framing options are the same as for [`CborCodec`](struct.CborCodec.html).
+/// ```
+/// # use futures::{executor, TryStreamExt};
+/// # use futures::io::Cursor;
+/// use futures_codec::{FramedRead, RawCborCodec};
+///
+/// executor::block_on(async move {
+///     // 1, then "a" with tag 32
+///     let input = Cursor::new(&b"\x01\xd8\x20\x61a"[..]);
+///     let mut framed = FramedRead::new(input, RawCborCodec::new());
+///
+///     assert_eq!(&framed.try_next().await.unwrap().unwrap()[..], b"\x01");
+///     assert_eq!(&framed.try_next().await.unwrap().unwrap()[..], b"\xd8\x20\x61a");
+/// });
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RawCborCodec {
+    framer: CborFramer,
+    self_describe: bool,
+}
+
+impl RawCborCodec {
+    /// Creates a new `RawCborCodec`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether each encoded item is prefixed with the self-describe
+    /// tag, unless it already starts with it
+    pub fn with_self_describe(mut self, self_describe: bool) -> Self {
+        self.self_describe = self_describe;
+        self
+    }
+
+    /// Sets the maximum length of an encoded item
+    pub fn with_max_frame_length(mut self, max: usize) -> Self {
+        self.framer.max_frame_length = Some(max);
+        self
+    }
+
+    /// Sets whether oversized items are skipped after returning the error,
+    /// so decoding can carry on with the next item
+    ///
+    /// Otherwise an oversized item is left in the buffer, and decoding
+    /// returns the same error again.
+    pub fn with_discard_oversized(mut self, discard: bool) -> Self {
+        self.framer.discard_oversized = discard;
+        self
+    }
+
+    /// Sets the maximum nesting depth of arrays, maps and tags
+    pub fn with_max_depth(mut self, max: usize) -> Self {
+        self.framer.limits.max_depth = Some(max);
+        self
+    }
+
+    /// Sets the maximum length of a byte or text string
+    pub fn with_max_string_length(mut self, max: usize) -> Self {
+        self.framer.limits.max_string_length = Some(max);
+        self
+    }
+
+    /// Sets the maximum number of items in an array or entries in a map
+    pub fn with_max_collection_length(mut self, max: usize) -> Self {
+        self.framer.limits.max_collection_length = Some(max);
+        self
+    }
+}
+
+impl Decoder for RawCborCodec {
+    type Item = Bytes;
+    type Error = CborCodecError;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let end = match self.framer.next_item(buf)? {
+            Some(end) => end,
+            None => return Ok(None),
+        };
+
+        let mut de = serde_cbor::Deserializer::from_slice(&buf[..end]);
+        if let Err(e) = IgnoredAny::deserialize(&mut de) {
+            buf.advance(end);
+            return Err(e.into());
+        }
+        Ok(Some(buf.split_to(end).freeze()))
+    }
+}
+
+impl Encoder for RawCborCodec {
+    type Item = Bytes;
+    type Error = CborCodecError;
+
+    fn encode(&mut self, data: Self::Item, buf: &mut BytesMut) -> Result<(), Self::Error> {
+        buf.reserve(SELF_DESCRIBE_TAG.len() + data.len());
+        if self.self_describe && !data.starts_with(SELF_DESCRIBE_TAG) {
+            buf.put_slice(SELF_DESCRIBE_TAG);
+        }
+        buf.put_slice(&data);
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use bytes::BytesMut;
+    use bytes::{Bytes, BytesMut};
+    use serde::ser::{Error, SerializeSeq, Serializer};
     use serde::{Deserialize, Serialize};
 
-    use super::CborCodec;
+    use super::{CborCodec, CborCodecError, RawCborCodec};
     use crate::{Decoder, Encoder};
 
     #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -180,6 +509,17 @@ mod test {
         pub data: u16,
     }
 
+    // Writes the start of a sequence before failing
+    struct Unserializable;
+
+    impl Serialize for Unserializable {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut seq = serializer.serialize_seq(Some(2))?;
+            seq.serialize_element(&1)?;
+            Err(S::Error::custom("unserializable"))
+        }
+    }
+
     #[test]
     fn cbor_codec_encode_decode() {
         let mut codec = CborCodec::<TestStruct, TestStruct>::new();
@@ -199,6 +539,47 @@ mod test {
         assert_eq!(buff.len(), 0);
     }
 
+    #[test]
+    fn cbor_codec_encode_error() {
+        let mut codec = CborCodec::<Unserializable, ()>::new().with_self_describe(true);
+        let mut buff = BytesMut::from(&b"\xf6"[..]);
+
+        assert!(matches!(
+            codec.encode(Unserializable, &mut buff),
+            Err(CborCodecError::Cbor(_))
+        ));
+        assert_eq!(&buff[..], b"\xf6");
+    }
+
+    #[test]
+    fn cbor_codec_skips_mismatched_items() {
+        let mut codec = CborCodec::<(), u32>::new();
+        // [1, 2], then 5
+        let mut buff = BytesMut::from(&b"\x82\x01\x02\x05"[..]);
+
+        assert!(matches!(
+            codec.decode(&mut buff),
+            Err(CborCodecError::Cbor(_))
+        ));
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(5));
+        assert_eq!(buff.len(), 0);
+    }
+
+    #[test]
+    fn cbor_codec_skips_malformed_headers() {
+        // Reserved additional information, then 1
+        let mut codec = CborCodec::<(), u32>::new();
+        let mut buff = BytesMut::from(&b"\x1c\x01"[..]);
+        assert!(codec.decode(&mut buff).is_err());
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(1));
+
+        let mut codec = RawCborCodec::new();
+        let mut buff = BytesMut::from(&b"\x1c\x01"[..]);
+        assert!(codec.decode(&mut buff).is_err());
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(Bytes::from("\x01")));
+        assert_eq!(buff.len(), 0);
+    }
+
     #[test]
     fn cbor_codec_partial_decode() {
         let mut codec = CborCodec::<TestStruct, TestStruct>::new();
@@ -217,4 +598,153 @@ mod test {
 
         assert_eq!(buff.len(), 0);
     }
+
+    #[test]
+    fn cbor_codec_discard_oversized() {
+        let mut codec = CborCodec::<TestStruct, TestStruct>::new()
+            .with_max_frame_length(16)
+            .with_discard_oversized(true);
+        let mut buff = BytesMut::new();
+
+        let item1 = TestStruct {
+            name: "A name longer than the maximum".to_owned(),
+            data: 1,
+        };
+        codec.encode(item1, &mut buff).unwrap();
+        let mut rest = buff.split_off(20);
+
+        match codec.decode(&mut buff) {
+            Err(CborCodecError::FrameTooLarge(e)) => assert_eq!(e.max, 16),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(buff.len(), 0);
+
+        let item2 = TestStruct {
+            name: "ok".to_owned(),
+            data: 2,
+        };
+        codec.encode(item2.clone(), &mut rest).unwrap();
+        assert_eq!(codec.decode(&mut rest).unwrap(), Some(item2));
+    }
+
+    #[test]
+    fn cbor_codec_complete_oversized() {
+        // "too long", then "ok"
+        let input = &b"\x68too long\x62ok"[..];
+
+        let mut codec = CborCodec::<(), String>::new().with_max_frame_length(4);
+        let mut buff = BytesMut::from(input);
+        for _ in 0..2 {
+            assert!(matches!(
+                codec.decode(&mut buff),
+                Err(CborCodecError::FrameTooLarge(_))
+            ));
+            assert_eq!(buff.len(), input.len());
+        }
+
+        let mut codec = codec.with_discard_oversized(true);
+        assert!(matches!(
+            codec.decode(&mut buff),
+            Err(CborCodecError::FrameTooLarge(_))
+        ));
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some("ok".to_owned()));
+    }
+
+    #[test]
+    fn cbor_codec_packed_self_describe() {
+        let mut codec = CborCodec::<TestStruct, TestStruct>::new()
+            .with_packed(true)
+            .with_self_describe(true);
+        let mut buff = BytesMut::new();
+
+        let item = TestStruct {
+            name: "Test name".to_owned(),
+            data: 16,
+        };
+        codec.encode(item.clone(), &mut buff).unwrap();
+        assert_eq!(&buff[..3], b"\xd9\xd9\xf7");
+        assert!(!buff.windows(4).any(|w| w == b"data"));
+
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(item));
+        assert_eq!(buff.len(), 0);
+    }
+
+    #[test]
+    fn cbor_codec_limits() {
+        let mut codec = CborCodec::<Vec<u8>, Vec<u8>>::new().with_max_collection_length(1024);
+        let mut buff = BytesMut::new();
+
+        codec.encode(vec![1, 2, 3], &mut buff).unwrap();
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(vec![1, 2, 3]));
+
+        // An array claiming 2^32 - 1 items is rejected from its header alone
+        buff.extend_from_slice(b"\x9a\xff\xff\xff\xff\x00");
+        assert!(matches!(
+            codec.decode(&mut buff),
+            Err(CborCodecError::CollectionTooLong { max: 1024 })
+        ));
+    }
+
+    #[test]
+    fn cbor_codec_values() {
+        use serde_cbor::Value;
+
+        let mut codec = CborCodec::<Value, Value>::new().with_self_describe(true);
+        let mut buff = BytesMut::new();
+
+        let items = vec![
+            Value::Integer(1),
+            Value::Text("two".to_owned()),
+            Value::Array(vec![Value::Bool(true), Value::Null]),
+        ];
+        for item in &items {
+            codec.encode(item.clone(), &mut buff).unwrap();
+        }
+        for item in items {
+            assert_eq!(codec.decode(&mut buff).unwrap(), Some(item));
+        }
+        assert_eq!(buff.len(), 0);
+    }
+
+    #[cfg(feature = "cbor-tags")]
+    #[test]
+    fn cbor_codec_keeps_tags() {
+        use serde_cbor::Value;
+
+        let mut codec = CborCodec::<Value, Value>::new();
+        let mut buff = BytesMut::from(&b"\xd9\xd9\xf7\xc1\x1a\x00\x00\x00\x01"[..]);
+
+        let item = Value::Tag(1, Box::new(Value::Integer(1)));
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(item.clone()));
+
+        codec.encode(item, &mut buff).unwrap();
+        assert_eq!(&buff[..], b"\xc1\x01");
+    }
+
+    #[test]
+    fn raw_cbor_codec() {
+        let mut codec = RawCborCodec::new().with_self_describe(true);
+        let mut buff = BytesMut::new();
+
+        // A tagged map, then a text string
+        let map = Bytes::from_static(b"\xd8\x20\xa1\x61a\x80");
+        codec.encode(map.clone(), &mut buff).unwrap();
+        codec
+            .encode(Bytes::from_static(b"\xd9\xd9\xf7\x61b"), &mut buff)
+            .unwrap();
+        assert_eq!(&buff[..3], b"\xd9\xd9\xf7");
+
+        let mut expected = BytesMut::from(&b"\xd9\xd9\xf7"[..]);
+        expected.extend_from_slice(&map);
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(expected.freeze()));
+        assert_eq!(
+            codec.decode(&mut buff).unwrap(),
+            Some(Bytes::from_static(b"\xd9\xd9\xf7\x61b"))
+        );
+        assert_eq!(buff.len(), 0);
+
+        // A reserved simple value header is malformed
+        buff.extend_from_slice(b"\xfc");
+        assert!(codec.decode(&mut buff).is_err());
+    }
 }
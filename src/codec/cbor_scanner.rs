@@ -0,0 +1,278 @@
+use super::limits::{LimitExceeded, Limits};
+use std::cmp;
+
+/// Finds the end of the first CBOR data item in a buffer without decoding it.
+///
+/// The scanner walks item headers, skipping over string payloads and keeping
+/// track of how many items each open array, map or tag still expects. The
+/// scan resumes where it stopped on the previous call, so the buffer must
+/// only grow between calls unless `consume` is used to account for bytes
+/// removed from its front.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct CborScanner {
+    offset: usize,
+    // Payload bytes of a definite length string left to skip
+    skip: u64,
+    open: Vec<Open>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Open {
+    // Number of items still expected by a definite length container or a tag
+    Items(u64),
+    // An indefinite length container, closed by a break. Counts the items
+    // read so far, and how many of them make up an entry.
+    Indefinite { items: u64, per_entry: u64 },
+    // An indefinite length string, with the length of its chunks so far
+    IndefiniteString(u64),
+}
+
+impl CborScanner {
+    /// Returns the end offset of the first item once it is complete
+    ///
+    /// Malformed input is reported as complete so that the deserializer,
+    /// rather than the scanner, describes the error.
+    pub(crate) fn scan(&mut self, buf: &[u8]) -> Option<usize> {
+        match self.scan_limited(buf, &Limits::default()) {
+            Ok(end) => end,
+            Err(_) => unreachable!("no limits to exceed"),
+        }
+    }
+
+    /// Like `scan`, failing as soon as the item exceeds one of `limits`
+    ///
+    /// Declared lengths are checked as soon as their header is read, before
+    /// the content is buffered. The scanner is reset after an error.
+    pub(crate) fn scan_limited(
+        &mut self,
+        buf: &[u8],
+        limits: &Limits,
+    ) -> Result<Option<usize>, LimitExceeded> {
+        let res = self.run(buf, limits);
+        if res.is_err() {
+            *self = Self::default();
+        }
+        res
+    }
+
+    fn run(&mut self, buf: &[u8], limits: &Limits) -> Result<Option<usize>, LimitExceeded> {
+        loop {
+            if self.skip > 0 {
+                let available = (buf.len() - self.offset) as u64;
+                let n = cmp::min(self.skip, available);
+                self.offset += n as usize;
+                self.skip -= n;
+                if self.skip > 0 {
+                    return Ok(None);
+                }
+                if self.close_item(limits)? {
+                    return Ok(Some(self.finish()));
+                }
+                continue;
+            }
+
+            let (major, info, value, len) = match read_header(&buf[self.offset..]) {
+                Header::Complete(major, info, value, len) => (major, info, value, len),
+                Header::Incomplete => return Ok(None),
+                Header::Malformed => {
+                    // Take in the bad byte, so it's consumed with the item
+                    self.offset += 1;
+                    return Ok(Some(self.finish()));
+                }
+            };
+            self.offset += len;
+
+            let done = match (major, info) {
+                // Break, closing the innermost indefinite length item
+                (7, 31) => match self.open.pop() {
+                    Some(Open::Indefinite { .. }) | Some(Open::IndefiniteString(_)) => {
+                        self.close_item(limits)?
+                    }
+                    _ => return Ok(Some(self.finish())),
+                },
+                (2, 31) | (3, 31) => {
+                    self.open.push(Open::IndefiniteString(0));
+                    false
+                }
+                (4, 31) | (5, 31) => {
+                    limits.check_depth(self.open.len() + 1)?;
+                    let per_entry = if major == 5 { 2 } else { 1 };
+                    self.open.push(Open::Indefinite {
+                        items: 0,
+                        per_entry,
+                    });
+                    false
+                }
+                (_, 31) => return Ok(Some(self.finish())),
+                (2, _) | (3, _) => {
+                    let total = match self.open.last_mut() {
+                        Some(Open::IndefiniteString(total)) => {
+                            *total = total.saturating_add(value);
+                            *total
+                        }
+                        _ => value,
+                    };
+                    limits.check_string_length(total)?;
+                    self.skip = value;
+                    self.skip == 0 && self.close_item(limits)?
+                }
+                (4, _) | (5, _) => {
+                    limits.check_collection_length(value)?;
+                    let items = if major == 5 {
+                        match value.checked_mul(2) {
+                            Some(items) => items,
+                            None => return Ok(Some(self.finish())),
+                        }
+                    } else {
+                        value
+                    };
+                    if items == 0 {
+                        self.close_item(limits)?
+                    } else {
+                        limits.check_depth(self.open.len() + 1)?;
+                        self.open.push(Open::Items(items));
+                        false
+                    }
+                }
+                (6, _) => {
+                    limits.check_depth(self.open.len() + 1)?;
+                    self.open.push(Open::Items(1));
+                    false
+                }
+                _ => self.close_item(limits)?,
+            };
+
+            if done {
+                return Ok(Some(self.finish()));
+            }
+        }
+    }
+
+    /// Accounts for `n` bytes removed from the front of the scanned buffer
+    pub(crate) fn consume(&mut self, n: usize) {
+        self.offset -= n;
+    }
+
+    // Records a complete item, returns whether the top-level item is done
+    fn close_item(&mut self, limits: &Limits) -> Result<bool, LimitExceeded> {
+        loop {
+            match self.open.last_mut() {
+                None => return Ok(true),
+                Some(Open::IndefiniteString(_)) => return Ok(false),
+                Some(Open::Indefinite { items, per_entry }) => {
+                    *items += 1;
+                    limits.check_collection_length(items.div_ceil(*per_entry))?;
+                    return Ok(false);
+                }
+                Some(Open::Items(n)) => {
+                    *n -= 1;
+                    if *n > 0 {
+                        return Ok(false);
+                    }
+                    // The container is itself a complete item of its parent
+                    self.open.pop();
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self) -> usize {
+        let end = self.offset;
+        *self = Self::default();
+        end
+    }
+}
+
+enum Header {
+    // Major type, additional information, argument and header length
+    Complete(u8, u8, u64, usize),
+    Incomplete,
+    Malformed,
+}
+
+fn read_header(buf: &[u8]) -> Header {
+    let initial = match buf.first() {
+        Some(b) => *b,
+        None => return Header::Incomplete,
+    };
+    let (major, info) = (initial >> 5, initial & 0x1f);
+
+    let arg_len = match info {
+        0..=23 | 31 => 0,
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        _ => return Header::Malformed,
+    };
+    if buf.len() < 1 + arg_len {
+        return Header::Incomplete;
+    }
+
+    let value = match info {
+        0..=23 => u64::from(info),
+        31 => 0,
+        _ => buf[1..=arg_len]
+            .iter()
+            .fold(0, |acc, b| (acc << 8) | u64::from(*b)),
+    };
+    Header::Complete(major, info, value, 1 + arg_len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::limits::{LimitExceeded, Limits};
+    use super::CborScanner;
+
+    #[test]
+    fn it_finds_the_end_of_nested_items() {
+        let mut scanner = CborScanner::default();
+        // {"a": [1, h'0102'], "b": (_ "c" "d")} followed by 0
+        let input = b"\xa2\x61a\x82\x01\x42\x01\x02\x61b\x7f\x61c\x61d\xff\x00";
+
+        assert_eq!(scanner.scan(&input[..7]), None);
+        assert_eq!(scanner.scan(input), Some(input.len() - 1));
+    }
+
+    #[test]
+    fn it_handles_tags_and_empty_containers() {
+        let mut scanner = CborScanner::default();
+
+        assert_eq!(scanner.scan(b"\xc1\x80\x00"), Some(2));
+        assert_eq!(scanner.scan(b"\x9f\xff\x00"), Some(2));
+    }
+
+    #[test]
+    fn it_enforces_limits() {
+        let limits = Limits {
+            max_depth: Some(2),
+            max_string_length: Some(3),
+            max_collection_length: Some(2),
+        };
+        let scan = |input: &[u8]| CborScanner::default().scan_limited(input, &limits);
+
+        // [{"a": h'010203'}, (_ "ab" "c")]
+        assert_eq!(
+            scan(b"\x82\xa1\x61a\x43\x01\x02\x03\x7f\x62ab\x61c\xff"),
+            Ok(Some(15))
+        );
+        // A declared length fails before its content arrives
+        assert_eq!(
+            scan(b"\x9a\xff\xff\xff\xff"),
+            Err(LimitExceeded::CollectionLength(2))
+        );
+        assert_eq!(
+            scan(b"\x5a\xff\xff\xff\xff"),
+            Err(LimitExceeded::StringLength(3))
+        );
+        assert_eq!(scan(b"\x81\x81\x81\x00"), Err(LimitExceeded::Depth(2)));
+        assert_eq!(
+            scan(b"\x9f\x00\x00\x00\xff"),
+            Err(LimitExceeded::CollectionLength(2))
+        );
+        assert_eq!(
+            scan(b"\x7f\x62ab\x62cd\xff"),
+            Err(LimitExceeded::StringLength(3))
+        );
+    }
+}
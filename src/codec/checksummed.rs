@@ -0,0 +1,305 @@
+use crate::{Decoder, Encoder};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::io::Error as IoError;
+
+/// Checksum algorithms available to [`Checksummed`](struct.Checksummed.html)
+///
+/// Each is enabled by the cargo feature of the same name: `crc32`, `crc32c`
+/// or `xxhash`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    /// CRC-32 (IEEE), as used by zlib and Ethernet, taking 4 bytes
+    #[cfg(feature = "crc32")]
+    Crc32,
+    /// CRC-32C (Castagnoli), as used by iSCSI and ext4, taking 4 bytes
+    #[cfg(feature = "crc32c")]
+    Crc32c,
+    /// 32-bit xxHash with a seed of 0, taking 4 bytes
+    #[cfg(feature = "xxhash")]
+    XxHash32,
+    /// 64-bit xxHash with a seed of 0, taking 8 bytes
+    #[cfg(feature = "xxhash")]
+    XxHash64,
+}
+
+impl ChecksumAlgorithm {
+    /// Number of bytes the checksum takes at the end of a frame
+    pub fn size(self) -> usize {
+        match self {
+            #[cfg(feature = "crc32")]
+            ChecksumAlgorithm::Crc32 => 4,
+            #[cfg(feature = "crc32c")]
+            ChecksumAlgorithm::Crc32c => 4,
+            #[cfg(feature = "xxhash")]
+            ChecksumAlgorithm::XxHash32 => 4,
+            #[cfg(feature = "xxhash")]
+            ChecksumAlgorithm::XxHash64 => 8,
+        }
+    }
+
+    /// Computes the checksum of `data`
+    pub fn checksum(self, data: &[u8]) -> u64 {
+        match self {
+            #[cfg(feature = "crc32")]
+            ChecksumAlgorithm::Crc32 => u64::from(crc32fast::hash(data)),
+            #[cfg(feature = "crc32c")]
+            ChecksumAlgorithm::Crc32c => u64::from(crc32c::crc32c(data)),
+            #[cfg(feature = "xxhash")]
+            ChecksumAlgorithm::XxHash32 => u64::from(xxhash_rust::xxh32::xxh32(data, 0)),
+            #[cfg(feature = "xxhash")]
+            ChecksumAlgorithm::XxHash64 => xxhash_rust::xxh64::xxh64(data, 0),
+        }
+    }
+}
+
+/// A codec wrapping another `Bytes` codec, adding a checksum to the end of
+/// each frame.
+///
+/// The checksum is written big-endian inside the inner codec's frame, so a
+/// length prefix written by the inner codec covers it too. Frames whose
+/// checksum doesn't match are consumed and returned as
+/// `ChecksummedError::Mismatch`, and the following frames still decode.
+///
+/// # Example
+///
+#[cfg_attr(feature = "crc32", doc = "```")]
+#[cfg_attr(not(feature = "crc32"), doc = "```ignore")]
+/// # futures::executor::block_on(async move {
+/// use bytes::Bytes;
+/// use futures::{SinkExt, TryStreamExt};
+/// use futures::io::Cursor;
+/// use futures_codec::{ChecksumAlgorithm, Checksummed, Framed, VarintLengthCodec};
+///
+/// let codec = Checksummed::new(VarintLengthCodec::new(), ChecksumAlgorithm::Crc32);
+/// let mut framed = Framed::new(Cursor::new(Vec::new()), codec);
+/// framed.send(Bytes::from("hello")).await?;
+///
+/// let (cur, codec) = framed.release();
+/// assert_eq!(cur.get_ref(), b"\x09hello\x36\x10\xa6\x86");
+///
+/// let mut framed = Framed::new(Cursor::new(cur.into_inner()), codec);
+/// assert_eq!(framed.try_next().await?, Some(Bytes::from("hello")));
+/// # Ok::<_, futures_codec::ChecksummedError<std::io::Error>>(())
+/// # }).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checksummed<C> {
+    inner: C,
+    algorithm: ChecksumAlgorithm,
+}
+
+impl<C> Checksummed<C> {
+    /// Creates a new `Checksummed` codec adding checksums to the frames of
+    /// `inner`
+    pub fn new(inner: C, algorithm: ChecksumAlgorithm) -> Self {
+        Self { inner, algorithm }
+    }
+
+    /// Returns the checksum algorithm in use
+    pub fn algorithm(&self) -> ChecksumAlgorithm {
+        self.algorithm
+    }
+
+    /// Returns a reference to the inner codec
+    pub fn get_ref(&self) -> &C {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner codec
+    pub fn get_mut(&mut self) -> &mut C {
+        &mut self.inner
+    }
+
+    /// Consumes the `Checksummed`, returning the inner codec
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+
+    fn verify<E>(&self, mut frame: Bytes) -> Result<Bytes, ChecksummedError<E>> {
+        let size = self.algorithm.size();
+        if frame.len() < size {
+            return Err(ChecksummedError::Truncated { len: frame.len() });
+        }
+
+        let expected = frame.split_off(frame.len() - size).get_uint(size);
+        let actual = self.algorithm.checksum(&frame);
+        if expected != actual {
+            return Err(ChecksummedError::Mismatch { expected, actual });
+        }
+        Ok(frame)
+    }
+}
+
+/// Checksummed codec error enumeration
+#[derive(Debug)]
+pub enum ChecksummedError<E> {
+    /// Error from the inner codec
+    Inner(E),
+    /// A frame was too short to hold a checksum
+    Truncated {
+        /// Length of the frame
+        len: usize,
+    },
+    /// A frame's checksum didn't match its contents
+    Mismatch {
+        /// Checksum read from the frame
+        expected: u64,
+        /// Checksum computed from the frame's contents
+        actual: u64,
+    },
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for ChecksummedError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChecksummedError::Inner(e) => write!(f, "{}", e),
+            ChecksummedError::Truncated { len } => {
+                write!(f, "frame of {} bytes is too short for a checksum", len)
+            }
+            ChecksummedError::Mismatch { expected, actual } => write!(
+                f,
+                "checksum mismatch: expected {:#x}, computed {:#x}",
+                expected, actual
+            ),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for ChecksummedError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ChecksummedError::Inner(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<E: From<IoError>> From<IoError> for ChecksummedError<E> {
+    fn from(e: IoError) -> ChecksummedError<E> {
+        ChecksummedError::Inner(e.into())
+    }
+}
+
+impl<C> Encoder for Checksummed<C>
+where
+    C: Encoder<Item = Bytes>,
+{
+    type Item = Bytes;
+    type Error = ChecksummedError<C::Error>;
+
+    fn encode(&mut self, src: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let size = self.algorithm.size();
+        let mut frame = BytesMut::with_capacity(src.len() + size);
+        frame.put_slice(&src);
+        frame.put_uint(self.algorithm.checksum(&src), size);
+
+        self.inner
+            .encode(frame.freeze(), dst)
+            .map_err(ChecksummedError::Inner)
+    }
+}
+
+impl<C> Decoder for Checksummed<C>
+where
+    C: Decoder<Item = Bytes>,
+{
+    type Item = Bytes;
+    type Error = ChecksummedError<C::Error>;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.inner.decode(src).map_err(ChecksummedError::Inner)? {
+            Some(frame) => self.verify(frame).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self
+            .inner
+            .decode_eof(src)
+            .map_err(ChecksummedError::Inner)?
+        {
+            Some(frame) => self.verify(frame).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LengthCodec, VarintLengthCodec};
+
+    fn algorithms() -> Vec<ChecksumAlgorithm> {
+        vec![
+            #[cfg(feature = "crc32")]
+            ChecksumAlgorithm::Crc32,
+            #[cfg(feature = "crc32c")]
+            ChecksumAlgorithm::Crc32c,
+            #[cfg(feature = "xxhash")]
+            ChecksumAlgorithm::XxHash32,
+            #[cfg(feature = "xxhash")]
+            ChecksumAlgorithm::XxHash64,
+        ]
+    }
+
+    #[test]
+    fn it_computes_known_checksums() {
+        for algorithm in algorithms() {
+            // The CRC check values, and xxHash of the empty input
+            let (input, expected): (&[u8], u64) = match algorithm {
+                #[cfg(feature = "crc32")]
+                ChecksumAlgorithm::Crc32 => (b"123456789", 0xcbf4_3926),
+                #[cfg(feature = "crc32c")]
+                ChecksumAlgorithm::Crc32c => (b"123456789", 0xe306_9283),
+                #[cfg(feature = "xxhash")]
+                ChecksumAlgorithm::XxHash32 => (b"", 0x02cc_5d05),
+                #[cfg(feature = "xxhash")]
+                ChecksumAlgorithm::XxHash64 => (b"", 0xef46_db37_51d8_e999),
+            };
+            assert_eq!(algorithm.checksum(input), expected);
+        }
+    }
+
+    #[test]
+    fn it_round_trips_frames() {
+        for algorithm in algorithms() {
+            let mut codec = Checksummed::new(LengthCodec::new(), algorithm);
+            let mut buf = BytesMut::new();
+
+            codec.encode(Bytes::from("hello"), &mut buf).unwrap();
+            codec.encode(Bytes::new(), &mut buf).unwrap();
+            assert_eq!(buf.len(), 2 * (8 + algorithm.size()) + 5);
+
+            assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::from("hello")));
+            assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::new()));
+            assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn it_reports_mismatches() {
+        for algorithm in algorithms() {
+            let mut codec = Checksummed::new(VarintLengthCodec::new(), algorithm);
+            let mut buf = BytesMut::new();
+
+            codec.encode(Bytes::from("hello"), &mut buf).unwrap();
+            codec.encode(Bytes::from("world"), &mut buf).unwrap();
+            buf[1] = b'j';
+            buf.extend_from_slice(b"\x01x");
+
+            match codec.decode(&mut buf) {
+                Err(ChecksummedError::Mismatch { expected, actual }) => {
+                    assert_eq!(expected, algorithm.checksum(b"hello"));
+                    assert_eq!(actual, algorithm.checksum(b"jello"));
+                }
+                res => panic!("unexpected result {:?}", res),
+            }
+            assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::from("world")));
+            assert!(matches!(
+                codec.decode(&mut buf),
+                Err(ChecksummedError::Truncated { len: 1 })
+            ));
+        }
+    }
+}
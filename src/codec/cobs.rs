@@ -0,0 +1,299 @@
+use super::FrameTooLarge;
+use crate::{Decoder, Encoder};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use memchr::memchr;
+use std::cmp;
+use std::io::{Error, ErrorKind};
+
+// Longest run of non-zero bytes a single code byte can cover
+const MAX_RUN: usize = 0xfe;
+
+/// A `Codec` implementation framing packets with Consistent Overhead Byte
+/// Stuffing, each followed by a `0x00` delimiter.
+///
+/// COBS removes every zero byte from a packet at the cost of at most one
+/// byte in 254, so a zero always marks the end of a frame. After line
+/// noise or a dropped byte, decoding picks up again at the next zero.
+/// Corrupt frames are consumed and returned as errors of kind
+/// `InvalidData`, and the following frames still decode. Empty frames,
+/// such as a zero sent to flush the receiver, are skipped.
+///
+/// # Example
+///
+/// ```
+/// # futures::executor::block_on(async move {
+/// use bytes::Bytes;
+/// use futures::{SinkExt, TryStreamExt};
+/// use futures::io::Cursor;
+/// use futures_codec::{CobsCodec, Framed};
+///
+/// let mut framed = Framed::new(Cursor::new(Vec::new()), CobsCodec::new());
+/// framed.send(Bytes::from_static(b"\x11\x00\x22")).await?;
+///
+/// let (cur, codec) = framed.release();
+/// assert_eq!(cur.get_ref(), b"\x02\x11\x02\x22\x00");
+///
+/// let mut framed = Framed::new(Cursor::new(cur.into_inner()), codec);
+/// assert_eq!(framed.try_next().await?, Some(Bytes::from_static(b"\x11\x00\x22")));
+/// # Ok::<_, std::io::Error>(())
+/// # }).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CobsCodec {
+    max_frame_length: Option<usize>,
+    discard_oversized: bool,
+    // Skipping the rest of an oversized frame
+    discarding: bool,
+    // Bytes of the buffer already known not to be a delimiter
+    next_index: usize,
+}
+
+impl CobsCodec {
+    /// Creates a new `CobsCodec` without a maximum frame length
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum length of an encoded frame, excluding its delimiter
+    ///
+    /// Decoding a longer frame returns a [`FrameTooLarge`] error, as soon as
+    /// more bytes than this are buffered without a delimiter. Encoding an
+    /// item whose encoded frame would be longer returns the same error.
+    pub fn with_max_frame_length(mut self, max: usize) -> Self {
+        self.max_frame_length = Some(max);
+        self
+    }
+
+    /// Sets whether oversized frames are skipped up to the next delimiter
+    /// after returning the error, so decoding can carry on with the next frame
+    ///
+    /// Otherwise an oversized frame is left in the buffer, and decoding
+    /// returns the same error again.
+    pub fn with_discard_oversized(mut self, discard: bool) -> Self {
+        self.discard_oversized = discard;
+        self
+    }
+}
+
+/// Longest COBS encoding of `len` bytes, excluding the delimiter
+fn max_encoded_len(len: usize) -> usize {
+    len + cmp::max(1, len.div_ceil(MAX_RUN))
+}
+
+/// Appends the COBS encoding of `src` to `dst`, without a delimiter
+pub(crate) fn cobs_encode(src: &[u8], dst: &mut BytesMut) {
+    dst.reserve(max_encoded_len(src.len()));
+
+    let mut runs = src.split(|b| *b == 0).peekable();
+    while let Some(run) = runs.next() {
+        let mut chunks = run.chunks(MAX_RUN).peekable();
+        if chunks.peek().is_none() {
+            dst.put_u8(1);
+        }
+        while let Some(chunk) = chunks.next() {
+            // A full run isn't followed by an implied zero, so when it ends
+            // on a zero that zero needs a code byte of its own
+            let full = chunk.len() == MAX_RUN;
+            dst.put_u8(chunk.len() as u8 + 1);
+            dst.put_slice(chunk);
+            if full && chunks.peek().is_none() && runs.peek().is_some() {
+                dst.put_u8(1);
+            }
+        }
+    }
+}
+
+/// Decodes a COBS encoded frame, excluding its delimiter
+pub(crate) fn cobs_decode(src: &[u8]) -> Result<BytesMut, Error> {
+    let mut dst = BytesMut::with_capacity(src.len());
+    let mut i = 0;
+    while i < src.len() {
+        let code = usize::from(src[i]);
+        let end = i + code;
+        if code == 0 || end > src.len() {
+            return Err(Error::new(ErrorKind::InvalidData, "invalid COBS frame"));
+        }
+        dst.put_slice(&src[i + 1..end]);
+        i = end;
+        if code <= MAX_RUN && i < src.len() {
+            dst.put_u8(0);
+        }
+    }
+    Ok(dst)
+}
+
+impl Encoder for CobsCodec {
+    type Item = Bytes;
+    type Error = Error;
+
+    fn encode(&mut self, src: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let start = dst.len();
+        cobs_encode(&src, dst);
+
+        if let Some(max) = self.max_frame_length {
+            let len = dst.len() - start;
+            if len > max {
+                dst.truncate(start);
+                let err = FrameTooLarge { len, max };
+                return Err(Error::new(ErrorKind::InvalidInput, err));
+            }
+        }
+
+        dst.put_u8(0);
+        Ok(())
+    }
+}
+
+impl Decoder for CobsCodec {
+    type Item = Bytes;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            if self.next_index > src.len() {
+                self.next_index = 0;
+            }
+            let end = match memchr(0, &src[self.next_index..]) {
+                Some(i) => self.next_index + i,
+                None => {
+                    self.next_index = src.len();
+                    if self.discarding {
+                        src.clear();
+                        self.next_index = 0;
+                        return Ok(None);
+                    }
+                    return match self.max_frame_length {
+                        Some(max) if src.len() > max => {
+                            let len = src.len();
+                            if self.discard_oversized {
+                                src.clear();
+                                self.next_index = 0;
+                                self.discarding = true;
+                            }
+                            Err(FrameTooLarge { len, max }.into())
+                        }
+                        _ => Ok(None),
+                    };
+                }
+            };
+            self.next_index = 0;
+
+            if self.discarding {
+                src.advance(end + 1);
+                self.discarding = false;
+                continue;
+            }
+            if let Some(max) = self.max_frame_length {
+                if end > max {
+                    if self.discard_oversized {
+                        src.advance(end + 1);
+                    }
+                    return Err(FrameTooLarge { len: end, max }.into());
+                }
+            }
+
+            let frame = src.split_to(end + 1);
+            if end == 0 {
+                continue;
+            }
+
+            return cobs_decode(&frame[..end]).map(|frame| Some(frame.freeze()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Vectors from the COBS paper and the Wikipedia article
+    const VECTORS: &[(&[u8], &[u8])] = &[
+        (b"", b"\x01"),
+        (b"\x00", b"\x01\x01"),
+        (b"\x00\x00", b"\x01\x01\x01"),
+        (b"\x11\x22\x00\x33", b"\x03\x11\x22\x02\x33"),
+        (b"\x11\x22\x33\x44", b"\x05\x11\x22\x33\x44"),
+        (b"\x11\x00\x00\x00", b"\x02\x11\x01\x01\x01"),
+    ];
+
+    #[test]
+    fn it_round_trips_vectors() {
+        for (decoded, encoded) in VECTORS {
+            let mut buf = BytesMut::new();
+            cobs_encode(decoded, &mut buf);
+            assert_eq!(&buf[..], *encoded);
+            assert_eq!(&cobs_decode(encoded).unwrap()[..], *decoded);
+        }
+    }
+
+    #[test]
+    fn it_round_trips_long_runs() {
+        let mut input: Vec<u8> = (1..=255).collect();
+        input.extend(1..=254);
+        input.push(0);
+        input.extend(1..=254);
+
+        for len in 0..=input.len() {
+            let mut buf = BytesMut::new();
+            cobs_encode(&input[..len], &mut buf);
+            assert!(!buf.contains(&0));
+            assert_eq!(&cobs_decode(&buf).unwrap()[..], &input[..len]);
+        }
+
+        let mut buf = BytesMut::new();
+        cobs_encode(&[1u8; 254], &mut buf);
+        assert_eq!(buf.len(), max_encoded_len(254));
+    }
+
+    #[test]
+    fn it_recovers_from_corrupt_frames() {
+        let mut codec = CobsCodec::new();
+        let mut src = BytesMut::from(&b"\x00\x05\x11\x00\x03\x11\x22\x00"[..]);
+
+        let err = codec.decode(&mut src).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            codec.decode(&mut src).unwrap(),
+            Some(Bytes::from_static(b"\x11\x22"))
+        );
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+    }
+
+    #[test]
+    fn it_discards_oversized_frames() {
+        let mut codec = CobsCodec::new()
+            .with_max_frame_length(3)
+            .with_discard_oversized(true);
+        let mut src = BytesMut::from(&b"\x05\x11\x22"[..]);
+
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+        src.extend_from_slice(b"\x33\x44");
+        let err = codec.decode(&mut src).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        src.extend_from_slice(b"\x00\x02\x11\x00");
+        assert_eq!(
+            codec.decode(&mut src).unwrap(),
+            Some(Bytes::from_static(b"\x11"))
+        );
+
+        let err = codec
+            .encode(Bytes::from_static(b"\x11\x22\x33"), &mut src)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn it_keeps_oversized_frames_without_discard() {
+        let mut codec = CobsCodec::new().with_max_frame_length(2);
+        let input = &b"\x04abc\x00\x02x\x00"[..];
+        let mut src = BytesMut::from(input);
+
+        for _ in 0..2 {
+            let err = codec.decode(&mut src).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+            assert_eq!(&src[..], input);
+        }
+    }
+}
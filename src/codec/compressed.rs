@@ -0,0 +1,445 @@
+use crate::{Decoder, Encoder};
+use bytes::{BufMut, Bytes, BytesMut};
+use std::io::{Error as IoError, ErrorKind};
+
+// Tag of frames stored without compression
+const STORED: u8 = 0;
+
+// An LZ4 block can't decompress to more than this many bytes per byte, as
+// each byte of a match length adds at most 255 to it
+#[cfg(feature = "lz4")]
+const LZ4_MAX_RATIO: usize = 255;
+
+/// Compression algorithms available to [`Compressed`](struct.Compressed.html)
+///
+/// Each is enabled by the cargo feature of the same name: `deflate`, `zstd`
+/// or `lz4`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionAlgorithm {
+    /// Raw deflate, as in RFC 1951, tagged `1`
+    #[cfg(feature = "deflate")]
+    Deflate,
+    /// Zstandard at its default level, tagged `2`
+    #[cfg(feature = "zstd")]
+    Zstd,
+    /// LZ4 block format preceded by the decompressed length as a
+    /// little-endian `u32`, tagged `3`
+    #[cfg(feature = "lz4")]
+    Lz4,
+}
+
+impl CompressionAlgorithm {
+    /// The byte identifying the algorithm at the start of a frame
+    pub fn tag(self) -> u8 {
+        match self {
+            #[cfg(feature = "deflate")]
+            CompressionAlgorithm::Deflate => 1,
+            #[cfg(feature = "zstd")]
+            CompressionAlgorithm::Zstd => 2,
+            #[cfg(feature = "lz4")]
+            CompressionAlgorithm::Lz4 => 3,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            #[cfg(feature = "deflate")]
+            1 => Some(CompressionAlgorithm::Deflate),
+            #[cfg(feature = "zstd")]
+            2 => Some(CompressionAlgorithm::Zstd),
+            #[cfg(feature = "lz4")]
+            3 => Some(CompressionAlgorithm::Lz4),
+            _ => None,
+        }
+    }
+
+    fn compress(self, data: &[u8]) -> Result<Vec<u8>, IoError> {
+        match self {
+            #[cfg(feature = "deflate")]
+            CompressionAlgorithm::Deflate => {
+                use std::io::Write;
+
+                let level = flate2::Compression::default();
+                let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), level);
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            #[cfg(feature = "zstd")]
+            CompressionAlgorithm::Zstd => zstd::stream::encode_all(data, 0),
+            #[cfg(feature = "lz4")]
+            CompressionAlgorithm::Lz4 => Ok(lz4_flex::compress_prepend_size(data)),
+        }
+    }
+
+    fn decompress<E>(self, data: &[u8], max: usize) -> Result<Vec<u8>, CompressedError<E>> {
+        match self {
+            #[cfg(feature = "deflate")]
+            CompressionAlgorithm::Deflate => {
+                read_limited(flate2::read::DeflateDecoder::new(data), max)
+            }
+            #[cfg(feature = "zstd")]
+            CompressionAlgorithm::Zstd => {
+                let decoder = zstd::stream::read::Decoder::with_buffer(data)
+                    .map_err(CompressedError::Compression)?;
+                read_limited(decoder, max)
+            }
+            #[cfg(feature = "lz4")]
+            CompressionAlgorithm::Lz4 => {
+                let invalid =
+                    |e| CompressedError::Compression(IoError::new(ErrorKind::InvalidData, e));
+                let (len, data) = lz4_flex::block::uncompressed_size(data).map_err(invalid)?;
+                if len > max {
+                    return Err(CompressedError::DecompressedTooLarge { max });
+                }
+                // The output is allocated up front, so don't trust a length
+                // the block is too short to hold
+                if len > data.len().saturating_mul(LZ4_MAX_RATIO) {
+                    let err = IoError::new(
+                        ErrorKind::InvalidData,
+                        "decompressed length is too large for the block",
+                    );
+                    return Err(CompressedError::Compression(err));
+                }
+                lz4_flex::decompress(data, len).map_err(invalid)
+            }
+        }
+    }
+}
+
+// Reads at most one byte more than `max`, to tell whether there was more
+#[cfg(any(feature = "deflate", feature = "zstd"))]
+fn read_limited<E>(reader: impl std::io::Read, max: usize) -> Result<Vec<u8>, CompressedError<E>> {
+    use std::io::Read;
+
+    let mut buf = Vec::new();
+    reader
+        .take((max as u64).saturating_add(1))
+        .read_to_end(&mut buf)
+        .map_err(CompressedError::Compression)?;
+    if buf.len() > max {
+        return Err(CompressedError::DecompressedTooLarge { max });
+    }
+    Ok(buf)
+}
+
+/// A codec wrapping another `Bytes` codec, compressing each frame on its
+/// own.
+///
+/// Each frame starts with a byte naming the algorithm it was compressed
+/// with, or `0` if it is stored as is. Frames shorter than the
+/// [threshold](#method.with_threshold), and frames that compression
+/// wouldn't make smaller, are stored. Decoding accepts any algorithm whose
+/// feature is enabled, not only the one used for encoding.
+///
+/// Set a [maximum decompressed length](#method.with_max_decompressed_length)
+/// when reading from untrusted peers, as a small frame can decompress to a
+/// huge one.
+///
+/// # Example
+///
+#[cfg_attr(feature = "deflate", doc = "```")]
+#[cfg_attr(not(feature = "deflate"), doc = "```ignore")]
+/// # futures::executor::block_on(async move {
+/// use bytes::Bytes;
+/// use futures::{SinkExt, TryStreamExt};
+/// use futures::io::Cursor;
+/// use futures_codec::{Compressed, CompressionAlgorithm, Framed, LengthCodec};
+///
+/// let codec = Compressed::new(LengthCodec::new(), CompressionAlgorithm::Deflate)
+///     .with_threshold(64)
+///     .with_max_decompressed_length(1024 * 1024);
+/// let mut framed = Framed::new(Cursor::new(Vec::new()), codec);
+/// framed.send(Bytes::from(vec![b'a'; 4096])).await?;
+///
+/// let (cur, codec) = framed.release();
+/// assert!(cur.get_ref().len() < 100);
+///
+/// let mut framed = Framed::new(Cursor::new(cur.into_inner()), codec);
+/// assert_eq!(framed.try_next().await?, Some(Bytes::from(vec![b'a'; 4096])));
+/// # Ok::<_, futures_codec::CompressedError<std::io::Error>>(())
+/// # }).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compressed<C> {
+    inner: C,
+    algorithm: CompressionAlgorithm,
+    threshold: usize,
+    max_decompressed_length: Option<usize>,
+}
+
+impl<C> Compressed<C> {
+    /// Creates a new `Compressed` codec compressing the frames of `inner`,
+    /// without a threshold or a maximum decompressed length
+    pub fn new(inner: C, algorithm: CompressionAlgorithm) -> Self {
+        Self {
+            inner,
+            algorithm,
+            threshold: 0,
+            max_decompressed_length: None,
+        }
+    }
+
+    /// Sets the length below which frames are stored without compression
+    pub fn with_threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Sets the maximum length of a decoded frame
+    ///
+    /// Decoding a frame that would be longer returns
+    /// `CompressedError::DecompressedTooLarge`, without decompressing more
+    /// than this.
+    pub fn with_max_decompressed_length(mut self, max: usize) -> Self {
+        self.max_decompressed_length = Some(max);
+        self
+    }
+
+    /// Returns the compression algorithm used for encoding
+    pub fn algorithm(&self) -> CompressionAlgorithm {
+        self.algorithm
+    }
+
+    /// Returns a reference to the inner codec
+    pub fn get_ref(&self) -> &C {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner codec
+    pub fn get_mut(&mut self) -> &mut C {
+        &mut self.inner
+    }
+
+    /// Consumes the `Compressed`, returning the inner codec
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+
+    fn decompress<E>(&self, frame: Bytes) -> Result<Bytes, CompressedError<E>> {
+        let max = self.max_decompressed_length.unwrap_or(usize::MAX);
+        let (&tag, data) = match frame.split_first() {
+            Some(split) => split,
+            None => {
+                let err = IoError::new(ErrorKind::InvalidData, "frame has no compression tag");
+                return Err(CompressedError::Compression(err));
+            }
+        };
+
+        if tag == STORED {
+            if data.len() > max {
+                return Err(CompressedError::DecompressedTooLarge { max });
+            }
+            return Ok(frame.slice(1..));
+        }
+
+        match CompressionAlgorithm::from_tag(tag) {
+            Some(algorithm) => algorithm.decompress(data, max).map(Bytes::from),
+            None => Err(CompressedError::UnknownAlgorithm(tag)),
+        }
+    }
+}
+
+/// Compressed codec error enumeration
+#[derive(Debug)]
+pub enum CompressedError<E> {
+    /// Error from the inner codec
+    Inner(E),
+    /// Compressing a frame failed, or a frame wasn't validly compressed
+    Compression(IoError),
+    /// A frame was tagged with an unknown or disabled algorithm
+    UnknownAlgorithm(u8),
+    /// A frame decompressed to more than the maximum decompressed length
+    DecompressedTooLarge {
+        /// Maximum decompressed length the codec was configured with
+        max: usize,
+    },
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for CompressedError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompressedError::Inner(e) => write!(f, "{}", e),
+            CompressedError::Compression(e) => write!(f, "compression error: {}", e),
+            CompressedError::UnknownAlgorithm(tag) => {
+                write!(f, "unknown compression algorithm {}", tag)
+            }
+            CompressedError::DecompressedTooLarge { max } => write!(
+                f,
+                "frame decompresses to more than the maximum of {} bytes",
+                max
+            ),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for CompressedError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CompressedError::Inner(ref e) => Some(e),
+            CompressedError::Compression(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<E: From<IoError>> From<IoError> for CompressedError<E> {
+    fn from(e: IoError) -> CompressedError<E> {
+        CompressedError::Inner(e.into())
+    }
+}
+
+impl<C> Encoder for Compressed<C>
+where
+    C: Encoder<Item = Bytes>,
+{
+    type Item = Bytes;
+    type Error = CompressedError<C::Error>;
+
+    fn encode(&mut self, src: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut compressed = None;
+        if src.len() >= self.threshold {
+            let data = self
+                .algorithm
+                .compress(&src)
+                .map_err(CompressedError::Compression)?;
+            if data.len() < src.len() {
+                compressed = Some(data);
+            }
+        }
+
+        let (tag, data) = match compressed {
+            Some(ref data) => (self.algorithm.tag(), &data[..]),
+            None => (STORED, &src[..]),
+        };
+        let mut frame = BytesMut::with_capacity(1 + data.len());
+        frame.put_u8(tag);
+        frame.put_slice(data);
+
+        self.inner
+            .encode(frame.freeze(), dst)
+            .map_err(CompressedError::Inner)
+    }
+}
+
+impl<C> Decoder for Compressed<C>
+where
+    C: Decoder<Item = Bytes>,
+{
+    type Item = Bytes;
+    type Error = CompressedError<C::Error>;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.inner.decode(src).map_err(CompressedError::Inner)? {
+            Some(frame) => self.decompress(frame).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.inner.decode_eof(src).map_err(CompressedError::Inner)? {
+            Some(frame) => self.decompress(frame).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LengthCodec;
+
+    fn algorithms() -> Vec<CompressionAlgorithm> {
+        vec![
+            #[cfg(feature = "deflate")]
+            CompressionAlgorithm::Deflate,
+            #[cfg(feature = "zstd")]
+            CompressionAlgorithm::Zstd,
+            #[cfg(feature = "lz4")]
+            CompressionAlgorithm::Lz4,
+        ]
+    }
+
+    #[test]
+    fn it_round_trips_frames() {
+        let large = Bytes::from(b"0123456789".repeat(100));
+        for algorithm in algorithms() {
+            let mut codec = Compressed::new(LengthCodec::new(), algorithm).with_threshold(16);
+            let mut buf = BytesMut::new();
+
+            codec.encode(large.clone(), &mut buf).unwrap();
+            assert_eq!(buf[8], algorithm.tag());
+            assert!(buf.len() < 100);
+            assert_eq!(codec.decode(&mut buf).unwrap(), Some(large.clone()));
+
+            // Short frames are stored, as is data that doesn't compress
+            codec.encode(Bytes::from("short"), &mut buf).unwrap();
+            assert_eq!(&buf[8..], b"\x00short");
+            assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::from("short")));
+
+            let noise: Vec<u8> = (0..64u32).map(|i| (i * 7919 % 251) as u8).collect();
+            codec.encode(Bytes::from(noise.clone()), &mut buf).unwrap();
+            assert_eq!(buf[8], STORED);
+            assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::from(noise)));
+        }
+    }
+
+    #[test]
+    fn it_limits_decompressed_length() {
+        let bomb = Bytes::from(vec![0u8; 1 << 20]);
+        for algorithm in algorithms() {
+            let mut codec = Compressed::new(LengthCodec::new(), algorithm);
+            let mut buf = BytesMut::new();
+            codec.encode(bomb.clone(), &mut buf).unwrap();
+            codec.encode(Bytes::from("next"), &mut buf).unwrap();
+
+            let mut codec = codec.with_max_decompressed_length(1024);
+            assert!(matches!(
+                codec.decode(&mut buf),
+                Err(CompressedError::DecompressedTooLarge { max: 1024 })
+            ));
+            assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::from("next")));
+        }
+    }
+
+    #[test]
+    fn it_rejects_invalid_frames() {
+        for algorithm in algorithms() {
+            let mut codec = Compressed::new(LengthCodec::new(), algorithm);
+            let mut buf = BytesMut::new();
+            let mut inner = LengthCodec::new();
+
+            inner.encode(Bytes::from("\x09data"), &mut buf).unwrap();
+            assert!(matches!(
+                codec.decode(&mut buf),
+                Err(CompressedError::UnknownAlgorithm(9))
+            ));
+
+            let mut frame = vec![algorithm.tag()];
+            frame.extend_from_slice(b"\xff\xff\xff\xff\xff\xff\xff\xff");
+            inner.encode(Bytes::from(frame), &mut buf).unwrap();
+            assert!(matches!(
+                codec.decode(&mut buf),
+                Err(CompressedError::Compression(_))
+            ));
+        }
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn it_rejects_lz4_lengths_the_block_cant_hold() {
+        let mut codec = Compressed::new(LengthCodec::new(), CompressionAlgorithm::Lz4);
+        let mut buf = BytesMut::new();
+
+        let frame = Bytes::from_static(b"\x03\xff\xff\xff\xff\x10a");
+        LengthCodec::new().encode(frame, &mut buf).unwrap();
+        match codec.decode(&mut buf) {
+            Err(CompressedError::Compression(e)) => assert_eq!(e.kind(), ErrorKind::InvalidData),
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        // The most compressible data still fits the bound
+        let zeros = Bytes::from(vec![0u8; 1 << 20]);
+        codec.encode(zeros.clone(), &mut buf).unwrap();
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(zeros));
+    }
+}
@@ -0,0 +1,43 @@
+use std::io::{Error, ErrorKind};
+
+/// Error returned by a decoder when a frame exceeds its maximum length
+///
+/// Codecs using `std::io::Error` wrap it in an error of kind `InvalidData`,
+/// from which it can be recovered with `get_ref` and `downcast_ref`.
+///
+/// ```
+/// use futures_codec::{BytesMut, Decoder, FrameTooLarge, LinesCodec};
+///
+/// let mut codec = LinesCodec::new().with_max_frame_length(4);
+/// let mut src = BytesMut::from("too long\n");
+///
+/// let err = codec.decode(&mut src).unwrap_err();
+/// let err = err.get_ref().unwrap().downcast_ref::<FrameTooLarge>().unwrap();
+/// assert_eq!(err.max, 4);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameTooLarge {
+    /// Length of the frame, or of the bytes buffered so far if the end of
+    /// the frame has not been seen yet
+    pub len: usize,
+    /// Maximum frame length the codec was configured with
+    pub max: usize,
+}
+
+impl std::fmt::Display for FrameTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "frame of {} bytes exceeds the maximum of {} bytes",
+            self.len, self.max
+        )
+    }
+}
+
+impl std::error::Error for FrameTooLarge {}
+
+impl From<FrameTooLarge> for Error {
+    fn from(e: FrameTooLarge) -> Error {
+        Error::new(ErrorKind::InvalidData, e)
+    }
+}
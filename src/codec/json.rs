@@ -1,12 +1,28 @@
+use std::cmp;
 use std::marker::PhantomData;
 
+use super::json_scanner::JsonScanner;
+use super::limits::{LimitExceeded, Limits};
+use super::{BytesLinesCodec, FrameTooLarge};
 use crate::{Decoder, Encoder};
-use bytes::{Buf, BufMut, BytesMut};
+use bytes::buf::BufMutExt;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use memchr::memchr2;
 
+use serde::de::IgnoredAny;
 use serde::{Deserialize, Serialize};
+use serde_json::error::Category;
 
 /// A codec for JSON encoding and decoding using serde_json
 /// Enc is the type to encode, Dec is the type to decode
+///
+/// Values are written back-to-back by default. In newline-delimited JSON
+/// mode, set with [`with_ndjson`](#method.with_ndjson), each value is
+/// followed by a `\n` and every line must hold exactly one value.
+///
+/// A malformed value normally leaves the rest of the stream undecodable.
+/// With [`with_skip_malformed`](#method.with_skip_malformed) the codec
+/// instead skips to the next newline or record separator and carries on.
 /// ```
 /// # use futures::{executor, SinkExt, TryStreamExt};
 /// # use futures::io::Cursor;
@@ -34,6 +50,8 @@ use serde::{Deserialize, Serialize};
 pub struct JsonCodec<Enc, Dec> {
     enc: PhantomData<Enc>,
     dec: PhantomData<Dec>,
+    framer: JsonFramer,
+    pretty: bool,
 }
 
 /// JSON Codec error enumeration
@@ -43,6 +61,41 @@ pub enum JsonCodecError {
     Io(std::io::Error),
     /// JSON error
     Json(serde_json::Error),
+    /// A value exceeded the maximum frame length
+    FrameTooLarge(FrameTooLarge),
+    /// A line did not hold exactly one JSON value in NDJSON mode
+    InvalidLine {
+        /// Number of the line in the stream, starting from 1
+        line: u64,
+        /// Content of the line, without its terminator
+        content: Bytes,
+        /// Error from parsing the line
+        error: serde_json::Error,
+    },
+    /// A value failed to parse and was skipped up to the next newline or
+    /// record separator, or on its own if it was well-formed, so decoding
+    /// can carry on
+    Malformed {
+        /// The skipped bytes, without the delimiter
+        content: Bytes,
+        /// Error from parsing the value
+        error: serde_json::Error,
+    },
+    /// A value was nested deeper than the maximum depth
+    NestingTooDeep {
+        /// The maximum depth
+        max: usize,
+    },
+    /// A string was longer than the maximum string length
+    StringTooLong {
+        /// The maximum length, in encoded bytes
+        max: usize,
+    },
+    /// An array or object held more items than the maximum collection length
+    CollectionTooLong {
+        /// The maximum number of items
+        max: usize,
+    },
 }
 
 impl std::fmt::Display for JsonCodecError {
@@ -50,6 +103,22 @@ impl std::fmt::Display for JsonCodecError {
         match self {
             JsonCodecError::Io(e) => write!(f, "I/O error: {}", e),
             JsonCodecError::Json(e) => write!(f, "JSON error: {}", e),
+            JsonCodecError::FrameTooLarge(e) => write!(f, "{}", e),
+            JsonCodecError::InvalidLine { line, error, .. } => {
+                write!(f, "JSON error on line {}: {}", line, error)
+            }
+            JsonCodecError::Malformed { error, .. } => {
+                write!(f, "skipped malformed JSON: {}", error)
+            }
+            JsonCodecError::NestingTooDeep { max } => {
+                write!(f, "value is nested deeper than {} levels", max)
+            }
+            JsonCodecError::StringTooLong { max } => {
+                write!(f, "string is longer than {} bytes", max)
+            }
+            JsonCodecError::CollectionTooLong { max } => {
+                write!(f, "collection has more than {} items", max)
+            }
         }
     }
 }
@@ -59,6 +128,12 @@ impl std::error::Error for JsonCodecError {
         match self {
             JsonCodecError::Io(ref e) => Some(e),
             JsonCodecError::Json(ref e) => Some(e),
+            JsonCodecError::FrameTooLarge(ref e) => Some(e),
+            JsonCodecError::InvalidLine { ref error, .. } => Some(error),
+            JsonCodecError::Malformed { ref error, .. } => Some(error),
+            JsonCodecError::NestingTooDeep { .. }
+            | JsonCodecError::StringTooLong { .. }
+            | JsonCodecError::CollectionTooLong { .. } => None,
         }
     }
 }
@@ -75,6 +150,22 @@ impl From<serde_json::Error> for JsonCodecError {
     }
 }
 
+impl From<FrameTooLarge> for JsonCodecError {
+    fn from(e: FrameTooLarge) -> JsonCodecError {
+        JsonCodecError::FrameTooLarge(e)
+    }
+}
+
+impl From<LimitExceeded> for JsonCodecError {
+    fn from(e: LimitExceeded) -> JsonCodecError {
+        match e {
+            LimitExceeded::Depth(max) => JsonCodecError::NestingTooDeep { max },
+            LimitExceeded::StringLength(max) => JsonCodecError::StringTooLong { max },
+            LimitExceeded::CollectionLength(max) => JsonCodecError::CollectionTooLong { max },
+        }
+    }
+}
+
 impl<Enc, Dec> JsonCodec<Enc, Dec>
 where
     for<'de> Dec: Deserialize<'de> + 'static,
@@ -85,8 +176,86 @@ where
         JsonCodec {
             enc: PhantomData,
             dec: PhantomData,
+            framer: JsonFramer::new(),
+            pretty: false,
         }
     }
+
+    /// Sets whether values are newline-delimited, as in NDJSON or JSON Lines
+    ///
+    /// Encoded values are followed by a `\n`. Decoded lines must hold exactly
+    /// one value, blank lines are skipped. A line which fails to parse is
+    /// consumed and reported as `JsonCodecError::InvalidLine`, so decoding
+    /// can carry on with the next line.
+    pub fn with_ndjson(mut self, ndjson: bool) -> Self {
+        self.framer.ndjson = ndjson;
+        self
+    }
+
+    /// Sets whether values are encoded as indented, multi-line JSON
+    ///
+    /// This has no effect in NDJSON mode, where a value must fit on one line.
+    pub fn with_pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+
+    /// Sets whether malformed values are skipped instead of ending the stream
+    ///
+    /// On a parse error the codec drops everything up to and including the
+    /// next `\n` or record separator (`0x1E`) and returns the dropped bytes
+    /// as `JsonCodecError::Malformed`. Decoding then resumes after the
+    /// delimiter. A well-formed value which doesn't deserialize to `Dec` is
+    /// dropped on its own, and decoding resumes right after it. This has no
+    /// effect in NDJSON mode, where a bad line is always skipped.
+    pub fn with_skip_malformed(mut self, skip: bool) -> Self {
+        self.framer.skip_malformed = skip;
+        self
+    }
+
+    /// Sets the maximum length of an encoded value
+    ///
+    /// Decoding a larger value returns `JsonCodecError::FrameTooLarge`, as
+    /// soon as more bytes than this are buffered for an incomplete value.
+    pub fn with_max_frame_length(mut self, max: usize) -> Self {
+        self.framer = self.framer.with_max_frame_length(max);
+        self
+    }
+
+    /// Sets whether oversized values are skipped after returning the error,
+    /// so decoding can carry on with the next value
+    ///
+    /// Otherwise an oversized value is left in the buffer, and decoding
+    /// returns the same error again.
+    pub fn with_discard_oversized(mut self, discard: bool) -> Self {
+        self.framer = self.framer.with_discard_oversized(discard);
+        self
+    }
+
+    /// Sets the maximum nesting depth of arrays and objects
+    ///
+    /// This and the other limits are checked before a value is deserialized,
+    /// see [`with_max_collection_length`](#method.with_max_collection_length).
+    pub fn with_max_depth(mut self, max: usize) -> Self {
+        self.framer.limits.max_depth = Some(max);
+        self
+    }
+
+    /// Sets the maximum length of a string, in encoded bytes
+    pub fn with_max_string_length(mut self, max: usize) -> Self {
+        self.framer.limits.max_string_length = Some(max);
+        self
+    }
+
+    /// Sets the maximum number of items in an array or members in an object
+    ///
+    /// A value exceeding a limit fails with the matching error as soon as
+    /// the excess is buffered, without being deserialized. It is not
+    /// consumed, except in NDJSON mode where its line is skipped.
+    pub fn with_max_collection_length(mut self, max: usize) -> Self {
+        self.framer.limits.max_collection_length = Some(max);
+        self
+    }
 }
 
 impl<Enc, Dec> Clone for JsonCodec<Enc, Dec>
@@ -94,9 +263,270 @@ where
     for<'de> Dec: Deserialize<'de> + 'static,
     for<'de> Enc: Serialize + 'static,
 {
-    /// Clone creates a new instance of the `JsonCodec`
+    /// Clone creates a new instance of the `JsonCodec` with the same configuration
     fn clone(&self) -> JsonCodec<Enc, Dec> {
-        JsonCodec::new()
+        JsonCodec {
+            enc: PhantomData,
+            dec: PhantomData,
+            framer: self.framer.clone(),
+            pretty: self.pretty,
+        }
+    }
+}
+
+pub(super) const RECORD_SEPARATOR: u8 = 0x1e;
+
+/// Splits a byte stream into JSON values, shared by the JSON codecs
+#[derive(Debug, Clone, PartialEq)]
+struct JsonFramer {
+    max_frame_length: Option<usize>,
+    discard_oversized: bool,
+    // Skipping the rest of an oversized value
+    discarding: bool,
+    scanner: JsonScanner,
+    limits: Limits,
+    skip_malformed: bool,
+    // Skipping the rest of a malformed value
+    resyncing: bool,
+    ndjson: bool,
+    lines: BytesLinesCodec,
+    // Number of lines read in NDJSON mode
+    line: u64,
+}
+
+/// A complete value found by `JsonFramer`, not parsed yet
+enum Frame {
+    /// A value taking up the first bytes of the buffer, up to this offset
+    Value(usize),
+    /// A line which was split off the buffer, without its terminator
+    Line(Bytes),
+}
+
+impl JsonFramer {
+    fn new() -> Self {
+        JsonFramer {
+            max_frame_length: None,
+            discard_oversized: false,
+            discarding: false,
+            scanner: JsonScanner::default(),
+            limits: Limits::default(),
+            skip_malformed: false,
+            resyncing: false,
+            ndjson: false,
+            lines: BytesLinesCodec::new().with_strip_terminator(true),
+            line: 0,
+        }
+    }
+
+    fn with_max_frame_length(mut self, max: usize) -> Self {
+        self.max_frame_length = Some(max);
+        self.lines = self.lines.with_max_frame_length(max);
+        self
+    }
+
+    fn with_discard_oversized(mut self, discard: bool) -> Self {
+        self.discard_oversized = discard;
+        self.lines = self.lines.with_discard_oversized(discard);
+        self
+    }
+
+    /// Finds the next complete frame and parses it with `parse`
+    ///
+    /// `parse` consumes the value from the buffer when it succeeds, which it
+    /// may do by splitting the value off. A value which fails to parse is
+    /// consumed here, so decoding can't get stuck on it.
+    fn decode<T>(
+        &mut self,
+        buf: &mut BytesMut,
+        eof: bool,
+        parse: impl FnOnce(&mut BytesMut, usize) -> Result<T, serde_json::Error>,
+        parse_line: impl FnOnce(&Bytes) -> Result<T, serde_json::Error>,
+    ) -> Result<Option<T>, JsonCodecError> {
+        let frame = if self.ndjson {
+            self.next_line(buf, eof)?
+        } else {
+            self.next_value(buf, eof)?
+        };
+        match frame {
+            None => Ok(None),
+            Some(Frame::Value(end)) => match parse(buf, end) {
+                Ok(v) => Ok(Some(v)),
+                Err(e) if self.skip_malformed => Err(self.malformed(buf, end, e)),
+                Err(e) => {
+                    buf.advance(end);
+                    Err(e.into())
+                }
+            },
+            Some(Frame::Line(content)) => match parse_line(&content) {
+                Ok(v) => Ok(Some(v)),
+                Err(error) => Err(JsonCodecError::InvalidLine {
+                    line: self.line,
+                    content,
+                    error,
+                }),
+            },
+        }
+    }
+
+    fn next_line(
+        &mut self,
+        buf: &mut BytesMut,
+        eof: bool,
+    ) -> Result<Option<Frame>, JsonCodecError> {
+        loop {
+            let res = if eof {
+                self.lines.decode_eof(buf)
+            } else {
+                self.lines.decode(buf)
+            };
+            let content = match res {
+                Ok(Some(content)) => content,
+                Ok(None) => return Ok(None),
+                Err(e) => {
+                    self.line += 1;
+                    return Err(lines_error(e));
+                }
+            };
+            self.line += 1;
+
+            if !content.iter().all(u8::is_ascii_whitespace) {
+                JsonScanner::default().scan_limited(&content, &self.limits)?;
+                return Ok(Some(Frame::Line(content)));
+            }
+        }
+    }
+
+    fn next_value(
+        &mut self,
+        buf: &mut BytesMut,
+        eof: bool,
+    ) -> Result<Option<Frame>, JsonCodecError> {
+        // Skip what remains of a malformed value
+        if self.resyncing {
+            match memchr2(b'\n', RECORD_SEPARATOR, buf) {
+                Some(i) => {
+                    buf.advance(i + 1);
+                    self.resyncing = false;
+                }
+                None => {
+                    buf.clear();
+                    return Ok(None);
+                }
+            }
+        }
+
+        // Skip what remains of an oversized value
+        if self.discarding {
+            match self.scanner.scan(buf) {
+                Some(end) => {
+                    buf.advance(end);
+                    self.discarding = false;
+                }
+                None => {
+                    let n = buf.len();
+                    buf.clear();
+                    self.scanner.consume(n);
+                    return Ok(None);
+                }
+            }
+        }
+
+        // Only parse once a whole value is buffered, the scanner picks up
+        // where it left off on the previous call
+        let mut end = self.scanner.scan_limited(buf, &self.limits)?;
+        if end.is_none() && eof {
+            end = self.scanner.end_of_input();
+        }
+        let end = match end {
+            Some(end) => end,
+            None if eof && buf.iter().all(u8::is_ascii_whitespace) => {
+                buf.clear();
+                self.scanner = JsonScanner::default();
+                return Ok(None);
+            }
+            None => {
+                if let Some(max) = self.max_frame_length {
+                    if buf.len() > max {
+                        let len = buf.len();
+                        return Err(self.too_large(buf, len, max));
+                    }
+                }
+                return Ok(None);
+            }
+        };
+
+        if let Some(max) = self.max_frame_length {
+            if end > max {
+                if self.discard_oversized {
+                    buf.advance(end);
+                }
+                return Err(FrameTooLarge { len: end, max }.into());
+            }
+        }
+        Ok(Some(Frame::Value(end)))
+    }
+
+    fn malformed(
+        &mut self,
+        buf: &mut BytesMut,
+        end: usize,
+        error: serde_json::Error,
+    ) -> JsonCodecError {
+        // The scanner found where a well-formed value ends, only its type
+        // was wrong
+        if error.classify() == Category::Data {
+            let content = buf.split_to(end).freeze();
+            return JsonCodecError::Malformed { content, error };
+        }
+
+        // serde_json reports 1-based lines and columns in bytes, column 0
+        // standing for the newline ending the previous line
+        let line_start = if error.line() > 1 {
+            buf.iter()
+                .enumerate()
+                .filter(|(_, b)| **b == b'\n')
+                .nth(error.line() - 2)
+                .map_or(buf.len(), |(i, _)| i + 1)
+        } else {
+            0
+        };
+        let pos = cmp::min((line_start + error.column()).saturating_sub(1), buf.len());
+
+        let content = match memchr2(b'\n', RECORD_SEPARATOR, &buf[pos..]) {
+            Some(i) => {
+                let content = buf.split_to(pos + i).freeze();
+                buf.advance(1);
+                content
+            }
+            None => {
+                self.resyncing = true;
+                buf.split().freeze()
+            }
+        };
+        JsonCodecError::Malformed { content, error }
+    }
+
+    fn too_large(&mut self, buf: &mut BytesMut, len: usize, max: usize) -> JsonCodecError {
+        if self.discard_oversized {
+            match self.scanner.scan(buf) {
+                Some(end) => buf.advance(end),
+                None => {
+                    let n = buf.len();
+                    buf.clear();
+                    self.scanner.consume(n);
+                    self.discarding = true;
+                }
+            }
+        }
+        FrameTooLarge { len, max }.into()
+    }
+}
+
+// Recovers the frame length error from the line splitter
+fn lines_error(e: std::io::Error) -> JsonCodecError {
+    match e.get_ref().and_then(|e| e.downcast_ref::<FrameTooLarge>()) {
+        Some(too_large) => JsonCodecError::FrameTooLarge(*too_large),
+        None => e.into(),
     }
 }
 
@@ -110,26 +540,35 @@ where
     type Error = JsonCodecError;
 
     fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        // Build streaming JSON iterator over data
-        let de = serde_json::Deserializer::from_slice(&buf);
-        let mut iter = de.into_iter::<Dec>();
-
-        // Attempt to fetch an item and generate response
-        let res = match iter.next() {
-            Some(Ok(v)) => Ok(Some(v)),
-            Some(Err(ref e)) if e.is_eof() => Ok(None),
-            Some(Err(e)) => Err(e.into()),
-            None => Ok(None),
-        };
+        self.framer
+            .decode(buf, false, parse_value, |line| serde_json::from_slice(line))
+    }
 
-        // Update offset from iterator
-        let offset = iter.byte_offset();
+    fn decode_eof(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.framer
+            .decode(buf, true, parse_value, |line| serde_json::from_slice(line))
+    }
+}
 
-        // Advance buffer
-        buf.advance(offset);
+// Parses the value at the front of the buffer, consuming it on success
+fn parse_value<T>(buf: &mut BytesMut, end: usize) -> Result<T, serde_json::Error>
+where
+    for<'de> T: Deserialize<'de>,
+{
+    let v = from_whole_slice(&buf[..end])?;
+    buf.advance(end);
+    Ok(v)
+}
 
-        res
-    }
+// Like `serde_json::from_slice`, which also rejects trailing characters
+pub(super) fn from_whole_slice<T>(v: &[u8]) -> Result<T, serde_json::Error>
+where
+    for<'de> T: Deserialize<'de>,
+{
+    let mut de = serde_json::Deserializer::from_slice(v);
+    let value = T::deserialize(&mut de)?;
+    de.end()?;
+    Ok(value)
 }
 
 /// Encoder impl encodes object streams to bytes
@@ -142,18 +581,28 @@ where
     type Error = JsonCodecError;
 
     fn encode(&mut self, data: Self::Item, buf: &mut BytesMut) -> Result<(), Self::Error> {
-        // Encode json
-        let j = serde_json::to_string(&data)?;
+        // Encode json straight into the buffer
+        let start = buf.len();
+        let writer = (&mut *buf).writer();
+        let res = if self.pretty && !self.framer.ndjson {
+            serde_json::to_writer_pretty(writer, &data)
+        } else {
+            serde_json::to_writer(writer, &data)
+        };
+        if let Err(e) = res {
+            // Don't leave part of the value in the buffer
+            buf.truncate(start);
+            return Err(e.into());
+        }
 
-        // Write to buffer
-        buf.reserve(j.len());
-        buf.put_slice(&j.as_bytes());
+        if self.framer.ndjson {
+            buf.put_u8(b'\n');
+        }
 
         Ok(())
     }
 }
 
-
 impl<Enc, Dec> Default for JsonCodec<Enc, Dec>
 where
     for<'de> Dec: Deserialize<'de> + 'static,
@@ -164,13 +613,160 @@ where
     }
 }
 
+/// A codec splitting a stream into JSON values without deserializing them
+///
+/// Each value is checked to be well-formed JSON and then yielded as `Bytes`,
+/// without surrounding whitespace. This suits proxies and routers which
+/// forward frames without knowing their schema. The encoder writes
+/// pre-serialized `Bytes` as they are, without checking them.
+///
+/// The framing options are the same as for [`JsonCodec`](struct.JsonCodec.html).
+/// ```
+/// # use futures::{executor, TryStreamExt};
+/// # use futures::io::Cursor;
+/// use futures_codec::{FramedRead, RawJsonCodec};
+///
+/// executor::block_on(async move {
+///     let input = Cursor::new(&b"{\"id\": 1} [true, null]\n"[..]);
+///     let mut framed = FramedRead::new(input, RawJsonCodec::new());
+///
+///     assert_eq!(&framed.try_next().await.unwrap().unwrap()[..], b"{\"id\": 1}");
+///     assert_eq!(&framed.try_next().await.unwrap().unwrap()[..], b"[true, null]");
+/// });
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawJsonCodec {
+    framer: JsonFramer,
+}
+
+impl RawJsonCodec {
+    /// Creates a new `RawJsonCodec`
+    pub fn new() -> Self {
+        RawJsonCodec {
+            framer: JsonFramer::new(),
+        }
+    }
+
+    /// Sets whether values are newline-delimited, as in NDJSON or JSON Lines
+    ///
+    /// See [`JsonCodec::with_ndjson`](struct.JsonCodec.html#method.with_ndjson).
+    pub fn with_ndjson(mut self, ndjson: bool) -> Self {
+        self.framer.ndjson = ndjson;
+        self
+    }
+
+    /// Sets whether malformed values are skipped instead of ending the stream
+    ///
+    /// See [`JsonCodec::with_skip_malformed`](struct.JsonCodec.html#method.with_skip_malformed).
+    pub fn with_skip_malformed(mut self, skip: bool) -> Self {
+        self.framer.skip_malformed = skip;
+        self
+    }
+
+    /// Sets the maximum length of an encoded value
+    pub fn with_max_frame_length(mut self, max: usize) -> Self {
+        self.framer = self.framer.with_max_frame_length(max);
+        self
+    }
+
+    /// Sets whether oversized values are skipped after returning the error,
+    /// so decoding can carry on with the next value
+    ///
+    /// Otherwise an oversized value is left in the buffer, and decoding
+    /// returns the same error again.
+    pub fn with_discard_oversized(mut self, discard: bool) -> Self {
+        self.framer = self.framer.with_discard_oversized(discard);
+        self
+    }
+
+    /// Sets the maximum nesting depth of arrays and objects
+    ///
+    /// This and the other limits are checked before a value is deserialized,
+    /// see [`with_max_collection_length`](#method.with_max_collection_length).
+    pub fn with_max_depth(mut self, max: usize) -> Self {
+        self.framer.limits.max_depth = Some(max);
+        self
+    }
+
+    /// Sets the maximum length of a string, in encoded bytes
+    pub fn with_max_string_length(mut self, max: usize) -> Self {
+        self.framer.limits.max_string_length = Some(max);
+        self
+    }
+
+    /// Sets the maximum number of items in an array or members in an object
+    ///
+    /// A value exceeding a limit fails with the matching error as soon as
+    /// the excess is buffered, without being deserialized. It is not
+    /// consumed, except in NDJSON mode where its line is skipped.
+    pub fn with_max_collection_length(mut self, max: usize) -> Self {
+        self.framer.limits.max_collection_length = Some(max);
+        self
+    }
+}
+
+impl Default for RawJsonCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Checks that the value at the front of the buffer is well-formed, then
+// splits it off without the whitespace before it
+fn split_value(buf: &mut BytesMut, end: usize) -> Result<Bytes, serde_json::Error> {
+    from_whole_slice::<IgnoredAny>(&buf[..end])?;
+    let start = buf[..end]
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(end);
+    buf.advance(start);
+    Ok(buf.split_to(end - start).freeze())
+}
+
+fn check_line(line: &Bytes) -> Result<Bytes, serde_json::Error> {
+    serde_json::from_slice::<IgnoredAny>(line)?;
+    let start = line.iter().position(|b| !b.is_ascii_whitespace());
+    let end = line.iter().rposition(|b| !b.is_ascii_whitespace());
+    match (start, end) {
+        (Some(start), Some(end)) => Ok(line.slice(start..=end)),
+        _ => Ok(Bytes::new()),
+    }
+}
+
+impl Decoder for RawJsonCodec {
+    type Item = Bytes;
+    type Error = JsonCodecError;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.framer.decode(buf, false, split_value, check_line)
+    }
+
+    fn decode_eof(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.framer.decode(buf, true, split_value, check_line)
+    }
+}
+
+impl Encoder for RawJsonCodec {
+    type Item = Bytes;
+    type Error = JsonCodecError;
+
+    fn encode(&mut self, data: Self::Item, buf: &mut BytesMut) -> Result<(), Self::Error> {
+        buf.reserve(data.len() + 1);
+        buf.put_slice(&data);
+        if self.framer.ndjson {
+            buf.put_u8(b'\n');
+        }
+        Ok(())
+    }
+}
 
 #[cfg(test)]
 mod test {
-    use bytes::BytesMut;
+    use bytes::{Bytes, BytesMut};
     use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
 
-    use super::JsonCodec;
+    use super::{JsonCodec, JsonCodecError, RawJsonCodec};
     use crate::{Decoder, Encoder};
 
     #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -198,6 +794,21 @@ mod test {
         assert_eq!(buff.len(), 0);
     }
 
+    #[test]
+    fn json_codec_encode_error() {
+        let mut codec = JsonCodec::<BTreeMap<Vec<u8>, u8>, ()>::new();
+        let mut buff = BytesMut::from(&b"{}"[..]);
+
+        // Keys must be strings, which is only found once "{" is written
+        let mut map = BTreeMap::new();
+        map.insert(vec![1], 1);
+        assert!(matches!(
+            codec.encode(map, &mut buff),
+            Err(JsonCodecError::Json(_))
+        ));
+        assert_eq!(&buff[..], b"{}");
+    }
+
     #[test]
     fn json_codec_partial_decode() {
         let mut codec = JsonCodec::<TestStruct, TestStruct>::new();
@@ -216,4 +827,258 @@ mod test {
 
         assert_eq!(buff.len(), 0);
     }
+
+    #[test]
+    fn json_codec_discard_oversized() {
+        let mut codec = JsonCodec::<TestStruct, TestStruct>::new()
+            .with_max_frame_length(30)
+            .with_discard_oversized(true);
+        let mut buff = BytesMut::new();
+
+        buff.extend_from_slice(br#"{"name": "a long name with a } inside","#);
+        match codec.decode(&mut buff) {
+            Err(JsonCodecError::FrameTooLarge(e)) => assert_eq!(e.max, 30),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(buff.len(), 0);
+
+        buff.extend_from_slice(br#" "data": 3} {"name": "ok", "data": 4}"#);
+        let item = codec.decode(&mut buff).unwrap().unwrap();
+        assert_eq!(item.data, 4);
+    }
+
+    #[test]
+    fn json_codec_complete_oversized() {
+        let input = &br#"{"name": "too long", "data": 3} {"name": "a", "data": 4}"#[..];
+
+        let mut codec = JsonCodec::<TestStruct, TestStruct>::new().with_max_frame_length(30);
+        let mut buff = BytesMut::from(input);
+        for _ in 0..2 {
+            assert!(matches!(
+                codec.decode(&mut buff),
+                Err(JsonCodecError::FrameTooLarge(_))
+            ));
+            assert_eq!(buff.len(), input.len());
+        }
+
+        let mut codec = codec.with_discard_oversized(true);
+        assert!(matches!(
+            codec.decode(&mut buff),
+            Err(JsonCodecError::FrameTooLarge(_))
+        ));
+        assert_eq!(codec.decode(&mut buff).unwrap().unwrap().data, 4);
+    }
+
+    #[test]
+    fn json_codec_ndjson() {
+        let mut codec = JsonCodec::<TestStruct, TestStruct>::new().with_ndjson(true);
+        let mut buff = BytesMut::new();
+
+        let item1 = TestStruct {
+            name: "Test name".to_owned(),
+            data: 16,
+        };
+        codec.encode(item1.clone(), &mut buff).unwrap();
+        assert_eq!(buff.last(), Some(&b'\n'));
+
+        buff.extend_from_slice(b"\n{\"name\": \"a\", \"data\": 1} {}\n");
+        codec.encode(item1.clone(), &mut buff).unwrap();
+
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(item1.clone()));
+        match codec.decode(&mut buff) {
+            Err(JsonCodecError::InvalidLine { line, content, .. }) => {
+                assert_eq!(line, 3);
+                assert_eq!(&content[..], b"{\"name\": \"a\", \"data\": 1} {}");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(item1));
+        assert_eq!(buff.len(), 0);
+    }
+
+    #[test]
+    fn json_codec_skip_malformed() {
+        let mut codec = JsonCodec::<TestStruct, TestStruct>::new().with_skip_malformed(true);
+        let mut buff = BytesMut::new();
+
+        let item = TestStruct {
+            name: "Test name".to_owned(),
+            data: 16,
+        };
+        buff.extend_from_slice(b"{\"name\": \"a\",\n \"data\": tru} trailing\n");
+        codec.encode(item.clone(), &mut buff).unwrap();
+        buff.extend_from_slice(b"{\"name\" oops}");
+
+        match codec.decode(&mut buff) {
+            Err(JsonCodecError::Malformed { content, .. }) => {
+                assert_eq!(&content[..], b"{\"name\": \"a\",\n \"data\": tru} trailing");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(item.clone()));
+
+        // The rest of the value is dropped up to the next record separator
+        assert!(matches!(
+            codec.decode(&mut buff),
+            Err(JsonCodecError::Malformed { .. })
+        ));
+        buff.extend_from_slice(b" more garbage\x1e");
+        codec.encode(item.clone(), &mut buff).unwrap();
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(item));
+        assert_eq!(buff.len(), 0);
+    }
+
+    #[test]
+    fn json_codec_consumes_mismatched() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct A {
+            a: u32,
+        }
+
+        let mut codec = JsonCodec::<(), A>::new();
+        let mut buff = BytesMut::from(&b"{\"a\":\"x\"} {\"a\":1}"[..]);
+
+        assert!(matches!(
+            codec.decode(&mut buff),
+            Err(JsonCodecError::Json(_))
+        ));
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(A { a: 1 }));
+        assert_eq!(buff.len(), 0);
+    }
+
+    #[test]
+    fn json_codec_skip_mismatched() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct A {
+            a: u32,
+        }
+
+        let mut codec = JsonCodec::<(), A>::new().with_skip_malformed(true);
+        let mut buff = BytesMut::from(&b"{\"a\":1}{\"b\":\"x\"}{\"a\":2}"[..]);
+
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(A { a: 1 }));
+        match codec.decode(&mut buff) {
+            Err(JsonCodecError::Malformed { content, .. }) => {
+                assert_eq!(&content[..], b"{\"b\":\"x\"}");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(A { a: 2 }));
+        assert_eq!(buff.len(), 0);
+    }
+
+    #[test]
+    fn json_codec_partial_values() {
+        let mut codec = JsonCodec::<TestStruct, TestStruct>::new();
+        let mut buff = BytesMut::new();
+
+        buff.extend_from_slice(b"{\"name\": \"a}\", ");
+        assert_eq!(codec.decode(&mut buff).unwrap(), None);
+        buff.extend_from_slice(b"\"data\": 1}\n");
+        assert_eq!(
+            codec.decode(&mut buff).unwrap(),
+            Some(TestStruct {
+                name: "a}".to_owned(),
+                data: 1,
+            })
+        );
+        assert_eq!(codec.decode_eof(&mut buff).unwrap(), None);
+        assert_eq!(buff.len(), 0);
+
+        // A bare number may go on, until the input ends
+        let mut codec = JsonCodec::<u32, u32>::new();
+        buff.extend_from_slice(b"12");
+        assert_eq!(codec.decode(&mut buff).unwrap(), None);
+        assert_eq!(codec.decode_eof(&mut buff).unwrap(), Some(12));
+    }
+
+    #[test]
+    fn raw_json_codec() {
+        let mut codec = RawJsonCodec::new().with_skip_malformed(true);
+        let mut buff = BytesMut::new();
+
+        codec
+            .encode(Bytes::from_static(b"{\"a\": [1, 2]}"), &mut buff)
+            .unwrap();
+        buff.extend_from_slice(b"\n {\"b\": nul}\n \"str\" 12");
+
+        assert_eq!(
+            codec.decode(&mut buff).unwrap(),
+            Some(Bytes::from_static(b"{\"a\": [1, 2]}"))
+        );
+        assert!(matches!(
+            codec.decode(&mut buff),
+            Err(JsonCodecError::Malformed { .. })
+        ));
+        assert_eq!(
+            codec.decode(&mut buff).unwrap(),
+            Some(Bytes::from_static(b"\"str\""))
+        );
+        assert_eq!(codec.decode(&mut buff).unwrap(), None);
+        assert_eq!(
+            codec.decode_eof(&mut buff).unwrap(),
+            Some(Bytes::from_static(b"12"))
+        );
+    }
+
+    #[test]
+    fn raw_json_codec_ndjson() {
+        let mut codec = RawJsonCodec::new().with_ndjson(true);
+        let mut buff = BytesMut::from(&b" [1, {}] \r\n{\n"[..]);
+
+        assert_eq!(
+            codec.decode(&mut buff).unwrap(),
+            Some(Bytes::from_static(b"[1, {}]"))
+        );
+        assert!(matches!(
+            codec.decode(&mut buff),
+            Err(JsonCodecError::InvalidLine { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn json_codec_pretty() {
+        let mut codec = JsonCodec::<TestStruct, TestStruct>::new().with_pretty(true);
+        let mut buff = BytesMut::new();
+
+        let item = TestStruct {
+            name: "Test name".to_owned(),
+            data: 16,
+        };
+        codec.encode(item.clone(), &mut buff).unwrap();
+        assert!(buff.contains(&b'\n'));
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(item));
+    }
+
+    #[test]
+    fn json_codec_limits() {
+        let mut codec = JsonCodec::<Vec<String>, Vec<String>>::new()
+            .with_max_depth(1)
+            .with_max_string_length(4)
+            .with_max_collection_length(2);
+        let mut buff = BytesMut::new();
+
+        buff.extend_from_slice(b"[\"abcd\", \"e\"] [[");
+        assert_eq!(
+            codec.decode(&mut buff).unwrap(),
+            Some(vec!["abcd".to_owned(), "e".to_owned()])
+        );
+        assert!(matches!(
+            codec.decode(&mut buff),
+            Err(JsonCodecError::NestingTooDeep { max: 1 })
+        ));
+
+        let mut codec = codec.with_ndjson(true);
+        buff.clear();
+        buff.extend_from_slice(b"[\"abcde\"]\n[\"a\", \"b\", \"c\"]\n[]\n");
+        assert!(matches!(
+            codec.decode(&mut buff),
+            Err(JsonCodecError::StringTooLong { max: 4 })
+        ));
+        assert!(matches!(
+            codec.decode(&mut buff),
+            Err(JsonCodecError::CollectionTooLong { max: 2 })
+        ));
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(vec![]));
+    }
 }
@@ -0,0 +1,202 @@
+use super::limits::{LimitExceeded, Limits};
+
+/// Finds the end of the first JSON value in a buffer without parsing it.
+///
+/// Only the structure of the input is tracked: nesting depth, strings and
+/// their escapes, and bare top-level scalars. The scan resumes where it
+/// stopped on the previous call, so the buffer must only grow between calls
+/// unless `consume` is used to account for bytes removed from its front.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct JsonScanner {
+    offset: usize,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    in_scalar: bool,
+    // Length of the current string, only tracked when it is limited
+    string_length: u64,
+    // Number of items in each open container, only tracked when it is limited
+    items: Vec<u64>,
+}
+
+impl JsonScanner {
+    /// Returns the end offset of the first value once it is complete
+    ///
+    /// Malformed input is reported as complete so that the deserializer,
+    /// rather than the scanner, describes the error.
+    pub(crate) fn scan(&mut self, buf: &[u8]) -> Option<usize> {
+        match self.scan_limited(buf, &Limits::default()) {
+            Ok(end) => end,
+            Err(_) => unreachable!("no limits to exceed"),
+        }
+    }
+
+    /// Like `scan`, failing as soon as the value exceeds one of `limits`
+    ///
+    /// The scanner is reset after an error.
+    pub(crate) fn scan_limited(
+        &mut self,
+        buf: &[u8],
+        limits: &Limits,
+    ) -> Result<Option<usize>, LimitExceeded> {
+        let res = self.run(buf, limits);
+        if res.is_err() {
+            *self = Self::default();
+        }
+        res
+    }
+
+    fn run(&mut self, buf: &[u8], limits: &Limits) -> Result<Option<usize>, LimitExceeded> {
+        let count_items = limits.max_collection_length.is_some();
+
+        while self.offset < buf.len() {
+            let b = buf[self.offset];
+            self.offset += 1;
+
+            if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if b == b'\\' {
+                    self.escaped = true;
+                } else if b == b'"' {
+                    self.in_string = false;
+                    if self.depth == 0 {
+                        return Ok(Some(self.finish()));
+                    }
+                    continue;
+                }
+                if limits.max_string_length.is_some() {
+                    self.string_length += 1;
+                    limits.check_string_length(self.string_length)?;
+                }
+                continue;
+            }
+
+            if self.in_scalar {
+                if is_whitespace(b) || is_structural(b) {
+                    // The terminating byte belongs to whatever comes next
+                    self.offset -= 1;
+                    return Ok(Some(self.finish()));
+                }
+                continue;
+            }
+
+            if count_items && !is_whitespace(b) {
+                self.count_item(b, limits)?;
+            }
+
+            match b {
+                b if is_whitespace(b) => {}
+                b'"' => {
+                    self.in_string = true;
+                    self.string_length = 0;
+                }
+                b'{' | b'[' => {
+                    self.depth += 1;
+                    limits.check_depth(self.depth)?;
+                    if count_items {
+                        self.items.push(0);
+                    }
+                }
+                b'}' | b']' => {
+                    if self.depth <= 1 {
+                        return Ok(Some(self.finish()));
+                    }
+                    self.depth -= 1;
+                    self.items.pop();
+                }
+                _ if self.depth == 0 => self.in_scalar = true,
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+
+    // Counts the items of the innermost container, as the separators
+    // between them plus one unless it is empty
+    fn count_item(&mut self, b: u8, limits: &Limits) -> Result<(), LimitExceeded> {
+        if let Some(items) = self.items.last_mut() {
+            match b {
+                b',' => *items += 1,
+                b'}' | b']' => return Ok(()),
+                _ if *items == 0 => *items = 1,
+                _ => return Ok(()),
+            }
+            limits.check_collection_length(*items)?;
+        }
+        Ok(())
+    }
+
+    /// Completes a bare top-level scalar once the input has ended
+    pub(crate) fn end_of_input(&mut self) -> Option<usize> {
+        if self.in_scalar {
+            Some(self.finish())
+        } else {
+            None
+        }
+    }
+
+    /// Accounts for `n` bytes removed from the front of the scanned buffer
+    pub(crate) fn consume(&mut self, n: usize) {
+        self.offset -= n;
+    }
+
+    fn finish(&mut self) -> usize {
+        let end = self.offset;
+        *self = Self::default();
+        end
+    }
+}
+
+fn is_whitespace(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\n' | b'\r')
+}
+
+fn is_structural(b: u8) -> bool {
+    matches!(b, b'{' | b'}' | b'[' | b']' | b',' | b':' | b'"')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::limits::{LimitExceeded, Limits};
+    use super::JsonScanner;
+
+    #[test]
+    fn it_finds_the_end_of_nested_values() {
+        let mut scanner = JsonScanner::default();
+        let input = br#" {"a": [1, "]\"}", {}]} {"#;
+
+        assert_eq!(scanner.scan(&input[..10]), None);
+        assert_eq!(scanner.scan(input), Some(23));
+    }
+
+    #[test]
+    fn it_needs_a_terminator_for_scalars() {
+        let mut scanner = JsonScanner::default();
+
+        assert_eq!(scanner.scan(b"123"), None);
+        assert_eq!(scanner.end_of_input(), Some(3));
+        assert_eq!(scanner.scan(b"123 "), Some(3));
+        assert_eq!(scanner.scan(b" "), None);
+        assert_eq!(scanner.end_of_input(), None);
+    }
+
+    #[test]
+    fn it_enforces_limits() {
+        let limits = Limits {
+            max_depth: Some(3),
+            max_string_length: Some(3),
+            max_collection_length: Some(2),
+        };
+        let scan = |input: &[u8]| JsonScanner::default().scan_limited(input, &limits);
+
+        assert_eq!(scan(br#"[{"abc": [1, 2]}, []] "#), Ok(Some(21)));
+        assert_eq!(scan(b"[[[[]]]]"), Err(LimitExceeded::Depth(3)));
+        assert_eq!(scan(br#"["abcd"]"#), Err(LimitExceeded::StringLength(3)));
+        assert_eq!(scan(b"[1, 2, 3]"), Err(LimitExceeded::CollectionLength(2)));
+        assert_eq!(
+            scan(br#"{"a": 1, "b": 2, "c": 3}"#),
+            Err(LimitExceeded::CollectionLength(2))
+        );
+    }
+}
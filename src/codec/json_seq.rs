@@ -0,0 +1,331 @@
+use std::marker::PhantomData;
+
+use super::json::{from_whole_slice, RECORD_SEPARATOR};
+use super::json_scanner::JsonScanner;
+use super::{FrameTooLarge, JsonCodecError};
+use crate::{Decoder, Encoder};
+use bytes::buf::BufMutExt;
+use bytes::{Buf, BufMut, BytesMut};
+use memchr::memchr;
+
+use serde::{Deserialize, Serialize};
+
+/// A codec for JSON text sequences, as defined by RFC 7464
+/// Enc is the type to encode, Dec is the type to decode
+///
+/// Each value is written as a record separator (`0x1E`), the JSON text and
+/// a `\n`. When decoding, a record which is cut short by the next separator
+/// or by the end of the stream is skipped, as the RFC recommends. This
+/// includes a bare number or literal which is not followed by whitespace,
+/// since it may have been truncated. A record which is complete but fails
+/// to parse is reported as `JsonCodecError::Malformed` and skipped.
+/// ```
+/// # futures::executor::block_on(async move {
+/// use futures::TryStreamExt;
+/// use futures::io::Cursor;
+/// use futures_codec::{FramedRead, JsonSeqCodec};
+///
+/// let input = Cursor::new(&b"\x1e{\"a\": 1\x1e[1, 2]\n\x1e3"[..]);
+/// let framed = FramedRead::new(input, JsonSeqCodec::<(), Vec<u32>>::new());
+///
+/// // The first and last records are truncated
+/// assert_eq!(framed.try_collect::<Vec<_>>().await.unwrap(), vec![vec![1, 2]]);
+/// # });
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct JsonSeqCodec<Enc, Dec> {
+    enc: PhantomData<Enc>,
+    dec: PhantomData<Dec>,
+    max_frame_length: Option<usize>,
+    discard_oversized: bool,
+    scanner: JsonScanner,
+    // Where to resume looking for the separator ending the current record
+    next_index: usize,
+}
+
+impl<Enc, Dec> JsonSeqCodec<Enc, Dec>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+{
+    /// Creates a new `JsonSeqCodec` with the associated types
+    pub fn new() -> JsonSeqCodec<Enc, Dec> {
+        JsonSeqCodec {
+            enc: PhantomData,
+            dec: PhantomData,
+            max_frame_length: None,
+            discard_oversized: false,
+            scanner: JsonScanner::default(),
+            next_index: 1,
+        }
+    }
+
+    /// Sets the maximum length of a record, excluding its separator
+    ///
+    /// Decoding a larger record returns `JsonCodecError::FrameTooLarge`, as
+    /// soon as more bytes than this are buffered for an incomplete record.
+    pub fn with_max_frame_length(mut self, max: usize) -> Self {
+        self.max_frame_length = Some(max);
+        self
+    }
+
+    /// Sets whether oversized records are skipped after returning the error,
+    /// so decoding can carry on with the next record
+    ///
+    /// Otherwise an oversized record is left in the buffer, and decoding
+    /// returns the same error again.
+    pub fn with_discard_oversized(mut self, discard: bool) -> Self {
+        self.discard_oversized = discard;
+        self
+    }
+
+    // Drops the start of the buffer up to the next separator, returns
+    // whether one was found
+    fn skip_record(&mut self, buf: &mut BytesMut) -> bool {
+        self.scanner = JsonScanner::default();
+        self.next_index = 1;
+        match memchr(RECORD_SEPARATOR, buf) {
+            Some(i) => {
+                buf.advance(i);
+                true
+            }
+            None => {
+                buf.clear();
+                false
+            }
+        }
+    }
+
+    fn decode_record(
+        &mut self,
+        buf: &mut BytesMut,
+        eof: bool,
+    ) -> Result<Option<Dec>, JsonCodecError> {
+        loop {
+            // Anything outside of a record, such as the `\n` ending the
+            // previous one, is skipped
+            if buf.first() != Some(&RECORD_SEPARATOR) && !self.skip_record(buf) {
+                return Ok(None);
+            }
+
+            if self.next_index > buf.len() {
+                self.next_index = 1;
+            }
+            let record_end = match memchr(RECORD_SEPARATOR, &buf[self.next_index..]) {
+                Some(i) => Some(self.next_index + i),
+                None => {
+                    self.next_index = buf.len();
+                    None
+                }
+            };
+            let record = &buf[1..record_end.unwrap_or_else(|| buf.len())];
+
+            let end = match self.scanner.scan(record) {
+                Some(end) => end,
+                // Cut short by the next record or the end of the stream
+                None if record_end.is_some() || eof => {
+                    buf.advance(1);
+                    self.skip_record(buf);
+                    continue;
+                }
+                None => {
+                    if let Some(max) = self.max_frame_length {
+                        if record.len() > max {
+                            let len = record.len();
+                            if self.discard_oversized {
+                                buf.advance(1);
+                                self.skip_record(buf);
+                            }
+                            return Err(FrameTooLarge { len, max }.into());
+                        }
+                    }
+                    return Ok(None);
+                }
+            };
+            self.next_index = 1;
+
+            if let Some(max) = self.max_frame_length {
+                if end > max {
+                    if self.discard_oversized {
+                        buf.advance(1 + end);
+                    }
+                    return Err(FrameTooLarge { len: end, max }.into());
+                }
+            }
+
+            let res = from_whole_slice(&record[..end]);
+            buf.advance(1);
+            let content = buf.split_to(end).freeze();
+            return match res {
+                Ok(v) => Ok(Some(v)),
+                Err(error) => Err(JsonCodecError::Malformed { content, error }),
+            };
+        }
+    }
+}
+
+impl<Enc, Dec> Clone for JsonSeqCodec<Enc, Dec>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+{
+    /// Clone creates a new instance of the `JsonSeqCodec` with the same configuration
+    fn clone(&self) -> JsonSeqCodec<Enc, Dec> {
+        JsonSeqCodec {
+            enc: PhantomData,
+            dec: PhantomData,
+            max_frame_length: self.max_frame_length,
+            discard_oversized: self.discard_oversized,
+            scanner: self.scanner.clone(),
+            next_index: self.next_index,
+        }
+    }
+}
+
+/// Decoder impl parses json records from bytes
+impl<Enc, Dec> Decoder for JsonSeqCodec<Enc, Dec>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+{
+    type Item = Dec;
+    type Error = JsonCodecError;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.decode_record(buf, false)
+    }
+
+    fn decode_eof(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.decode_record(buf, true)
+    }
+}
+
+/// Encoder impl writes each object as a record
+impl<Enc, Dec> Encoder for JsonSeqCodec<Enc, Dec>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+{
+    type Item = Enc;
+    type Error = JsonCodecError;
+
+    fn encode(&mut self, data: Self::Item, buf: &mut BytesMut) -> Result<(), Self::Error> {
+        let start = buf.len();
+        buf.put_u8(RECORD_SEPARATOR);
+        if let Err(e) = serde_json::to_writer((&mut *buf).writer(), &data) {
+            // Don't leave part of the record in the buffer
+            buf.truncate(start);
+            return Err(e.into());
+        }
+        buf.put_u8(b'\n');
+
+        Ok(())
+    }
+}
+
+impl<Enc, Dec> Default for JsonSeqCodec<Enc, Dec>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::BytesMut;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    use super::JsonSeqCodec;
+    use crate::{Decoder, Encoder, JsonCodecError};
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct TestStruct {
+        pub name: String,
+        pub data: u16,
+    }
+
+    #[test]
+    fn json_seq_codec_encode_decode() {
+        let mut codec = JsonSeqCodec::<TestStruct, TestStruct>::new();
+        let mut buff = BytesMut::new();
+
+        let item = TestStruct {
+            name: "Test name".to_owned(),
+            data: 16,
+        };
+        codec.encode(item.clone(), &mut buff).unwrap();
+        assert_eq!(buff.first(), Some(&0x1e));
+        assert_eq!(buff.last(), Some(&b'\n'));
+
+        // Split in the middle of the value
+        let mut rest = buff.split_off(10);
+        assert_eq!(codec.decode(&mut buff).unwrap(), None);
+        buff.unsplit(rest.split());
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(item));
+        assert_eq!(codec.decode_eof(&mut buff).unwrap(), None);
+        assert_eq!(buff.len(), 0);
+    }
+
+    #[test]
+    fn json_seq_codec_encode_error() {
+        let mut codec = JsonSeqCodec::<BTreeMap<Vec<u8>, u8>, ()>::new();
+        let mut buff = BytesMut::new();
+
+        let mut map = BTreeMap::new();
+        map.insert(vec![1], 1);
+        assert!(matches!(
+            codec.encode(map, &mut buff),
+            Err(JsonCodecError::Json(_))
+        ));
+        assert!(buff.is_empty());
+    }
+
+    #[test]
+    fn json_seq_codec_skips_truncated_records() {
+        let mut codec = JsonSeqCodec::<u32, u32>::new();
+        let mut buff = BytesMut::from(&b"junk\x1e12\x1e\x1e3\n\x1e\"a\"\n\x1e4"[..]);
+
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(3));
+        assert!(matches!(
+            codec.decode(&mut buff),
+            Err(JsonCodecError::Malformed { .. })
+        ));
+        assert_eq!(codec.decode(&mut buff).unwrap(), None);
+        assert_eq!(codec.decode_eof(&mut buff).unwrap(), None);
+        assert_eq!(buff.len(), 0);
+    }
+
+    #[test]
+    fn json_seq_codec_discard_oversized() {
+        let mut codec = JsonSeqCodec::<Vec<u32>, Vec<u32>>::new()
+            .with_max_frame_length(8)
+            .with_discard_oversized(true);
+        let mut buff = BytesMut::from(&b"\x1e[1, 2, 3, 4"[..]);
+
+        assert!(matches!(
+            codec.decode(&mut buff),
+            Err(JsonCodecError::FrameTooLarge(_))
+        ));
+        buff.extend_from_slice(b", 5]\n\x1e[6]\n");
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(vec![6]));
+    }
+
+    #[test]
+    fn json_seq_codec_keeps_oversized() {
+        let mut codec = JsonSeqCodec::<Vec<u32>, Vec<u32>>::new().with_max_frame_length(8);
+        let input = &b"\x1e[1, 2, 3, 4, 5]\n\x1e[6]\n"[..];
+        let mut buff = BytesMut::from(input);
+
+        for _ in 0..2 {
+            assert!(matches!(
+                codec.decode(&mut buff),
+                Err(JsonCodecError::FrameTooLarge(_))
+            ));
+            assert_eq!(&buff[..], input);
+        }
+    }
+}
@@ -1,11 +1,18 @@
+use super::LengthDelimitedCodec;
 use crate::{Decoder, Encoder};
-use bytes::{Buf, BufMut, Bytes, BytesMut};
+use bytes::{Bytes, BytesMut};
 use std::io::Error;
 
 const U64_LENGTH: usize = std::mem::size_of::<u64>();
 
 /// A simple `Codec` implementation sending your data by prefixing it by its length.
 ///
+/// The length is written as a big-endian `u64`. This is a preset of
+/// [`LengthDelimitedCodec`], which should be used for other header layouts.
+///
+/// The length sent by the peer is trusted unless a maximum is set with
+/// [`with_max_frame_length`](#method.with_max_frame_length).
+///
 /// # Example
 ///
 /// This codec will most likely be used wrapped in another codec like so.
@@ -44,17 +51,50 @@ const U64_LENGTH: usize = std::mem::size_of::<u64>();
 ///     }
 /// }
 /// ```
-pub struct LengthCodec;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LengthCodec {
+    inner: LengthDelimitedCodec,
+}
+
+impl LengthCodec {
+    /// Creates a new `LengthCodec` without a maximum frame length
+    pub fn new() -> Self {
+        let inner = LengthDelimitedCodec::builder()
+            .length_field_length(U64_LENGTH)
+            .big_endian()
+            .new_codec();
+        Self { inner }
+    }
+
+    /// Sets the maximum length of a frame, excluding its header
+    ///
+    /// Decoding a frame with a larger length returns a
+    /// [`FrameTooLarge`](crate::FrameTooLarge) error.
+    pub fn with_max_frame_length(mut self, max: usize) -> Self {
+        self.inner.max_frame_length = Some(max);
+        self
+    }
+
+    /// Sets whether oversized frames are skipped after returning the error,
+    /// so decoding can carry on with the next frame
+    pub fn with_discard_oversized(mut self, discard: bool) -> Self {
+        self.inner.discard_oversized = discard;
+        self
+    }
+}
+
+impl Default for LengthCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Encoder for LengthCodec {
     type Item = Bytes;
     type Error = Error;
 
     fn encode(&mut self, src: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        dst.reserve(U64_LENGTH + src.len());
-        dst.put_u64(src.len() as u64);
-        dst.extend_from_slice(&src);
-        Ok(())
+        self.inner.encode(src, dst)
     }
 }
 
@@ -63,34 +103,21 @@ impl Decoder for LengthCodec {
     type Error = Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        if src.len() < U64_LENGTH {
-            return Ok(None);
-        }
-
-        let mut len_bytes = [0u8; U64_LENGTH];
-        len_bytes.copy_from_slice(&src[..U64_LENGTH]);
-        let len = u64::from_be_bytes(len_bytes) as usize;
-
-        if src.len() - U64_LENGTH >= len {
-            // Skip the length header we already read.
-            src.advance(U64_LENGTH);
-            Ok(Some(src.split_to(len).freeze()))
-        } else {
-            Ok(None)
-        }
+        self.inner.decode(src)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use bytes::BufMut;
 
     mod decode {
         use super::*;
 
         #[test]
         fn it_returns_bytes_withouth_length_header() {
-            let mut codec = LengthCodec {};
+            let mut codec = LengthCodec::new();
 
             let mut src = BytesMut::with_capacity(5);
             src.put(&[0, 0, 0, 0, 0, 0, 0, 3u8, 1, 2, 3, 4][..]);
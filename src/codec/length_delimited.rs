@@ -0,0 +1,433 @@
+use super::FrameTooLarge;
+use crate::{Decoder, Encoder};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::cmp;
+use std::io::{Error, ErrorKind};
+
+/// A `Codec` implementation for frames prefixed by a configurable length header.
+///
+/// The header layout is described by a [`LengthDelimitedCodecBuilder`], obtained through
+/// [`LengthDelimitedCodec::builder`]. By default the header is a 4 byte big-endian
+/// length field which counts only the payload, and is stripped from decoded frames.
+///
+/// # Example
+///
+/// A 2 byte little-endian length field:
+///
+/// ```
+/// # futures::executor::block_on(async move {
+/// use bytes::Bytes;
+/// use futures::{SinkExt, TryStreamExt};
+/// use futures::io::Cursor;
+/// use futures_codec::{Framed, LengthDelimitedCodec};
+///
+/// let codec = LengthDelimitedCodec::builder()
+///     .length_field_length(2)
+///     .little_endian()
+///     .new_codec();
+///
+/// let mut framed = Framed::new(Cursor::new(Vec::new()), codec);
+/// framed.send(Bytes::from("hello")).await?;
+///
+/// let (cur, codec) = framed.release();
+/// assert_eq!(cur.get_ref(), b"\x05\x00hello");
+///
+/// let mut framed = Framed::new(Cursor::new(cur.into_inner()), codec);
+/// assert_eq!(framed.try_next().await?, Some(Bytes::from("hello")));
+/// # Ok::<_, std::io::Error>(())
+/// # }).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LengthDelimitedCodec {
+    length_field_offset: usize,
+    length_field_length: usize,
+    length_adjustment: isize,
+    length_includes_header: bool,
+    num_skip: Option<usize>,
+    big_endian: bool,
+    pub(super) max_frame_length: Option<usize>,
+    pub(super) discard_oversized: bool,
+    // Bytes left to skip from an oversized frame
+    discarding: usize,
+}
+
+/// Configures the header layout of a [`LengthDelimitedCodec`].
+///
+/// When decoding, a frame is made of `length_field_offset` bytes, the length
+/// field itself and the number of bytes given by the length field. The value
+/// of the length field is first corrected by `length_adjustment`, then by
+/// the size of the header if `length_includes_header` is set.
+///
+/// When encoding, the `length_field_offset` bytes preceding the length field
+/// are written as zeros, so a frame decodes with the same configuration.
+///
+/// The length of a frame, as compared to `max_frame_length`, is the number
+/// of bytes following the header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LengthDelimitedCodecBuilder {
+    codec: LengthDelimitedCodec,
+}
+
+impl LengthDelimitedCodec {
+    /// Creates a new `LengthDelimitedCodec` with the default configuration
+    pub fn new() -> Self {
+        Self::builder().new_codec()
+    }
+
+    /// Returns a builder to configure the header layout
+    pub fn builder() -> LengthDelimitedCodecBuilder {
+        LengthDelimitedCodecBuilder {
+            codec: LengthDelimitedCodec {
+                length_field_offset: 0,
+                length_field_length: 4,
+                length_adjustment: 0,
+                length_includes_header: false,
+                num_skip: None,
+                big_endian: true,
+                max_frame_length: None,
+                discard_oversized: false,
+                discarding: 0,
+            },
+        }
+    }
+
+    fn header_len(&self) -> usize {
+        self.length_field_offset + self.length_field_length
+    }
+}
+
+impl Default for LengthDelimitedCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LengthDelimitedCodecBuilder {
+    /// Sets the number of bytes preceding the length field, defaults to 0
+    pub fn length_field_offset(mut self, offset: usize) -> Self {
+        self.codec.length_field_offset = offset;
+        self
+    }
+
+    /// Sets the width of the length field in bytes, defaults to 4
+    ///
+    /// # Panics
+    ///
+    /// Panics if `length` is not one of 1, 2, 3, 4 or 8.
+    pub fn length_field_length(mut self, length: usize) -> Self {
+        assert!(
+            [1, 2, 3, 4, 8].contains(&length),
+            "invalid length field length: {}",
+            length
+        );
+        self.codec.length_field_length = length;
+        self
+    }
+
+    /// Sets a value added to the length field to get the number of bytes
+    /// following the header, defaults to 0
+    pub fn length_adjustment(mut self, adjustment: isize) -> Self {
+        self.codec.length_adjustment = adjustment;
+        self
+    }
+
+    /// Sets whether the length field counts the header as well as the
+    /// payload, defaults to `false`
+    pub fn length_includes_header(mut self, includes: bool) -> Self {
+        self.codec.length_includes_header = includes;
+        self
+    }
+
+    /// Sets the number of bytes stripped from the start of each decoded
+    /// frame, defaults to the size of the header
+    pub fn num_skip(mut self, num_skip: usize) -> Self {
+        self.codec.num_skip = Some(num_skip);
+        self
+    }
+
+    /// Reads and writes the length field in big-endian order, the default
+    pub fn big_endian(mut self) -> Self {
+        self.codec.big_endian = true;
+        self
+    }
+
+    /// Reads and writes the length field in little-endian order
+    pub fn little_endian(mut self) -> Self {
+        self.codec.big_endian = false;
+        self
+    }
+
+    /// Sets the maximum length of a frame, unbounded by default
+    ///
+    /// Decoding a frame with a larger length returns a [`FrameTooLarge`]
+    /// error, as does encoding an item larger than this.
+    pub fn max_frame_length(mut self, max: usize) -> Self {
+        self.codec.max_frame_length = Some(max);
+        self
+    }
+
+    /// Sets whether oversized frames are skipped, defaults to `false`
+    ///
+    /// When set, the [`FrameTooLarge`] error is returned once and the frame
+    /// is discarded so decoding can carry on with the next one. Otherwise
+    /// the error is returned without consuming any input.
+    pub fn discard_oversized(mut self, discard: bool) -> Self {
+        self.codec.discard_oversized = discard;
+        self
+    }
+
+    /// Creates a `LengthDelimitedCodec` with this configuration
+    pub fn new_codec(self) -> LengthDelimitedCodec {
+        self.codec
+    }
+}
+
+impl Encoder for LengthDelimitedCodec {
+    type Item = Bytes;
+    type Error = Error;
+
+    fn encode(&mut self, src: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        if let Some(max) = self.max_frame_length {
+            if src.len() > max {
+                let err = FrameTooLarge {
+                    len: src.len(),
+                    max,
+                };
+                return Err(Error::new(ErrorKind::InvalidInput, err));
+            }
+        }
+
+        let mut len = src.len() as i128 - self.length_adjustment as i128;
+        if self.length_includes_header {
+            len += self.header_len() as i128;
+        }
+
+        let max = match self.length_field_length {
+            8 => u64::MAX as i128,
+            n => (1i128 << (n * 8)) - 1,
+        };
+        if len < 0 || len > max {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "frame length does not fit in the length field",
+            ));
+        }
+
+        dst.reserve(self.header_len() + src.len());
+        dst.resize(dst.len() + self.length_field_offset, 0);
+        if self.big_endian {
+            dst.put_uint(len as u64, self.length_field_length);
+        } else {
+            dst.put_uint_le(len as u64, self.length_field_length);
+        }
+        dst.extend_from_slice(&src);
+        Ok(())
+    }
+}
+
+impl Decoder for LengthDelimitedCodec {
+    type Item = Bytes;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if self.discarding > 0 {
+            let n = cmp::min(self.discarding, src.len());
+            src.advance(n);
+            self.discarding -= n;
+            if self.discarding > 0 {
+                return Ok(None);
+            }
+        }
+
+        let header_len = self.header_len();
+        if src.len() < header_len {
+            return Ok(None);
+        }
+
+        let mut field = &src[self.length_field_offset..header_len];
+        let len = if self.big_endian {
+            field.get_uint(self.length_field_length)
+        } else {
+            field.get_uint_le(self.length_field_length)
+        };
+
+        let mut len = len as i128 + self.length_adjustment as i128;
+        if self.length_includes_header {
+            len -= header_len as i128;
+        }
+        if len < 0 || len > (usize::MAX - header_len) as i128 {
+            return Err(Error::new(ErrorKind::InvalidData, "invalid frame length"));
+        }
+
+        let len = len as usize;
+        if let Some(max) = self.max_frame_length {
+            if len > max {
+                if self.discard_oversized {
+                    self.discarding = header_len + len;
+                    let n = cmp::min(self.discarding, src.len());
+                    src.advance(n);
+                    self.discarding -= n;
+                }
+                return Err(FrameTooLarge { len, max }.into());
+            }
+        }
+
+        let frame_len = header_len + len;
+        let num_skip = self.num_skip.unwrap_or(header_len);
+        if num_skip > frame_len {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "frame is shorter than the bytes to skip",
+            ));
+        }
+
+        // The buffer isn't reserved up front, as the length comes from the peer
+        if src.len() < frame_len {
+            return Ok(None);
+        }
+
+        let mut frame = src.split_to(frame_len);
+        frame.advance(num_skip);
+        Ok(Some(frame.freeze()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod decode {
+        use super::*;
+
+        #[test]
+        fn it_honours_offset_adjustment_and_skip() {
+            // 1 byte type, 2 byte length counting the whole frame, 3 byte payload
+            let mut codec = LengthDelimitedCodec::builder()
+                .length_field_offset(1)
+                .length_field_length(2)
+                .length_includes_header(true)
+                .num_skip(0)
+                .new_codec();
+
+            let mut src = BytesMut::from(&[7u8, 0, 6, 1, 2, 3, 9][..]);
+            let item = codec.decode(&mut src).unwrap();
+
+            assert_eq!(item, Some(Bytes::from(&[7u8, 0, 6, 1, 2, 3][..])));
+            assert_eq!(&src[..], &[9u8][..]);
+        }
+
+        #[test]
+        fn it_waits_for_the_whole_frame() {
+            let mut codec = LengthDelimitedCodec::builder()
+                .length_field_length(3)
+                .little_endian()
+                .new_codec();
+
+            let mut src = BytesMut::from(&[2u8, 0, 0, 1][..]);
+            assert_eq!(codec.decode(&mut src).unwrap(), None);
+
+            src.put_u8(2);
+            let item = codec.decode(&mut src).unwrap();
+            assert_eq!(item, Some(Bytes::from(&[1u8, 2][..])));
+        }
+
+        #[test]
+        fn it_does_not_allocate_for_announced_lengths() {
+            let mut codec = LengthDelimitedCodec::builder()
+                .length_field_length(8)
+                .new_codec();
+
+            let mut src = BytesMut::from(&[0xffu8, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xf0][..]);
+            assert_eq!(codec.decode(&mut src).unwrap(), None);
+            assert!(src.capacity() < 1024);
+        }
+
+        #[test]
+        fn it_rejects_negative_lengths() {
+            let mut codec = LengthDelimitedCodec::builder()
+                .length_field_length(1)
+                .length_adjustment(-2)
+                .new_codec();
+
+            let mut src = BytesMut::from(&[1u8, 0][..]);
+            let err = codec.decode(&mut src).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+        }
+
+        #[test]
+        fn it_discards_oversized_frames() {
+            let mut codec = LengthDelimitedCodec::builder()
+                .length_field_length(1)
+                .max_frame_length(2)
+                .discard_oversized(true)
+                .new_codec();
+
+            let mut src = BytesMut::from(&[3u8, 1, 2][..]);
+            let err = codec.decode(&mut src).unwrap_err();
+            let err = err.get_ref().unwrap().downcast_ref::<FrameTooLarge>();
+            assert_eq!(err, Some(&FrameTooLarge { len: 3, max: 2 }));
+            assert!(src.is_empty());
+
+            src.put(&[3u8, 2, 4, 5][..]);
+            let item = codec.decode(&mut src).unwrap();
+            assert_eq!(item, Some(Bytes::from(&[4u8, 5][..])));
+        }
+
+        #[test]
+        fn it_keeps_oversized_frames_without_discard() {
+            let mut codec = LengthDelimitedCodec::builder()
+                .length_field_length(1)
+                .max_frame_length(2)
+                .new_codec();
+
+            let mut src = BytesMut::from(&[3u8, 1, 2, 3][..]);
+            assert!(codec.decode(&mut src).is_err());
+            assert!(codec.decode(&mut src).is_err());
+            assert_eq!(src.len(), 4);
+        }
+    }
+
+    mod encode {
+        use super::*;
+
+        #[test]
+        fn it_writes_the_adjusted_length() {
+            let mut codec = LengthDelimitedCodec::builder()
+                .length_field_length(2)
+                .length_includes_header(true)
+                .new_codec();
+
+            let mut dst = BytesMut::new();
+            codec.encode(Bytes::from("abc"), &mut dst).unwrap();
+            assert_eq!(&dst[..], b"\x00\x05abc");
+        }
+
+        #[test]
+        fn it_round_trips_with_an_offset() {
+            let mut codec = LengthDelimitedCodec::builder()
+                .length_field_offset(2)
+                .length_field_length(2)
+                .length_includes_header(true)
+                .new_codec();
+
+            let mut buf = BytesMut::new();
+            codec.encode(Bytes::from("abc"), &mut buf).unwrap();
+            assert_eq!(&buf[..], b"\x00\x00\x00\x07abc");
+            assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::from("abc")));
+            assert!(buf.is_empty());
+        }
+
+        #[test]
+        fn it_rejects_frames_too_large_for_the_field() {
+            let mut codec = LengthDelimitedCodec::builder()
+                .length_field_length(1)
+                .new_codec();
+
+            let mut dst = BytesMut::new();
+            let err = codec
+                .encode(Bytes::from(vec![0u8; 256]), &mut dst)
+                .unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        }
+    }
+}
@@ -0,0 +1,38 @@
+/// Structural limits checked by the scanners before a value is deserialized
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Limits {
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) max_string_length: Option<usize>,
+    pub(crate) max_collection_length: Option<usize>,
+}
+
+/// A limit which a value exceeded, along with its maximum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LimitExceeded {
+    Depth(usize),
+    StringLength(usize),
+    CollectionLength(usize),
+}
+
+impl Limits {
+    pub(crate) fn check_depth(&self, depth: usize) -> Result<(), LimitExceeded> {
+        match self.max_depth {
+            Some(max) if depth > max => Err(LimitExceeded::Depth(max)),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_string_length(&self, len: u64) -> Result<(), LimitExceeded> {
+        match self.max_string_length {
+            Some(max) if len > max as u64 => Err(LimitExceeded::StringLength(max)),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_collection_length(&self, len: u64) -> Result<(), LimitExceeded> {
+        match self.max_collection_length {
+            Some(max) if len > max as u64 => Err(LimitExceeded::CollectionLength(max)),
+            _ => Ok(()),
+        }
+    }
+}
@@ -1,32 +1,120 @@
+use super::FrameTooLarge;
 use crate::{Decoder, Encoder};
-use bytes::{BufMut, BytesMut};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use memchr::memchr;
 use std::io::{Error, ErrorKind};
 
 /// A simple `Codec` implementation that splits up data into lines.
 ///
+/// By default lines are decoded with their trailing `\n` and encoded as-is.
+/// Bytes left after the last newline when the stream ends are decoded as a
+/// final line, unless [`with_require_terminator`](#method.with_require_terminator)
+/// is set. Lines which are not valid UTF-8 are an error, unless
+/// [`with_lossy_utf8`](#method.with_lossy_utf8) is set.
+///
+/// [`BytesLinesCodec`] offers the same options for lines decoded as `Bytes`.
+/// Line-based protocols such as SMTP or IRC can strip the terminators when
+/// decoding and append `\r\n` when encoding:
+///
+/// ```rust
+/// use futures_codec::{BytesMut, Decoder, Encoder, LinesCodec};
+///
+/// let mut codec = LinesCodec::new()
+///     .with_strip_terminator(true)
+///     .with_terminator("\r\n")
+///     .with_max_frame_length(512);
+///
+/// let mut buf = BytesMut::from("EHLO example.com\r\n");
+/// let line = codec.decode(&mut buf)?.unwrap();
+/// assert_eq!(line, "EHLO example.com");
+///
+/// codec.encode("250 OK".to_owned(), &mut buf)?;
+/// assert_eq!(&buf[..], b"250 OK\r\n");
+/// # Ok::<_, std::io::Error>(())
+/// ```
+///
 /// ```rust
 /// # futures::executor::block_on(async move {
 /// use futures::stream::TryStreamExt; // for lines.try_next()
 /// use futures_codec::{FramedRead, LinesCodec};
 ///
 /// let input = "hello\nworld\nthis\nis\ndog\n".as_bytes();
-/// let mut lines = FramedRead::new(input, LinesCodec);
+/// let mut lines = FramedRead::new(input, LinesCodec::new());
 /// while let Some(line) = lines.try_next().await? {
 ///     println!("{}", line);
 /// }
 /// # Ok::<_, std::io::Error>(())
 /// # }).unwrap();
 /// ```
-pub struct LinesCodec;
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinesCodec {
+    inner: BytesLinesCodec,
+    lossy_utf8: bool,
+}
+
+impl LinesCodec {
+    /// Creates a new `LinesCodec` without a maximum line length
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether decoded lines have their trailing `\n` or `\r\n` removed
+    pub fn with_strip_terminator(mut self, strip: bool) -> Self {
+        self.inner = self.inner.with_strip_terminator(strip);
+        self
+    }
+
+    /// Sets whether a final line without a `\n` at the end of the stream is
+    /// an error, rather than decoded as any other line
+    pub fn with_require_terminator(mut self, require: bool) -> Self {
+        self.inner = self.inner.with_require_terminator(require);
+        self
+    }
+
+    /// Sets a terminator, such as `\n` or `\r\n`, appended to each line when
+    /// encoding
+    pub fn with_terminator(mut self, terminator: impl Into<String>) -> Self {
+        self.inner = self.inner.with_terminator(terminator.into());
+        self
+    }
+
+    /// Sets the maximum length of a line, excluding its `\n` or `\r\n`
+    ///
+    /// Decoding a longer line returns a [`FrameTooLarge`] error, as soon as
+    /// more bytes than this are buffered without a newline.
+    pub fn with_max_frame_length(mut self, max: usize) -> Self {
+        self.inner = self.inner.with_max_frame_length(max);
+        self
+    }
+
+    /// Sets whether oversized lines are skipped up to the next newline after
+    /// returning the error, so decoding can carry on with the next line
+    pub fn with_discard_oversized(mut self, discard: bool) -> Self {
+        self.inner = self.inner.with_discard_oversized(discard);
+        self
+    }
+
+    /// Sets whether invalid UTF-8 sequences are replaced with `U+FFFD`
+    /// rather than failing the line
+    pub fn with_lossy_utf8(mut self, lossy: bool) -> Self {
+        self.lossy_utf8 = lossy;
+        self
+    }
+
+    fn decode_utf8(&self, buf: Bytes) -> Result<String, Error> {
+        if self.lossy_utf8 {
+            return Ok(String::from_utf8_lossy(&buf).into_owned());
+        }
+        String::from_utf8(buf.to_vec()).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+}
 
 impl Encoder for LinesCodec {
     type Item = String;
     type Error = Error;
 
     fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        dst.reserve(item.len());
-        dst.put(item.as_bytes());
+        self.inner.encode_line(item.as_bytes(), dst);
         Ok(())
     }
 }
@@ -36,14 +124,307 @@ impl Decoder for LinesCodec {
     type Error = Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        match memchr(b'\n', src) {
+        match self.inner.decode(src)? {
+            Some(line) => self.decode_utf8(line).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.inner.decode_eof(src)? {
+            Some(line) => self.decode_utf8(line).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// A `Codec` implementation that splits up data into lines of `Bytes`.
+///
+/// Lines are split off the read buffer without being copied or checked for
+/// UTF-8. It has the same options as [`LinesCodec`].
+///
+/// ```rust
+/// # futures::executor::block_on(async move {
+/// use futures::stream::TryStreamExt;
+/// use futures_codec::{Bytes, BytesLinesCodec, FramedRead};
+///
+/// let input = &b"caf\xe9\r\n"[..];
+/// let codec = BytesLinesCodec::new().with_strip_terminator(true);
+/// let mut lines = FramedRead::new(input, codec);
+///
+/// assert_eq!(lines.try_next().await?, Some(Bytes::from(&b"caf\xe9"[..])));
+/// # Ok::<_, std::io::Error>(())
+/// # }).unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BytesLinesCodec {
+    strip_terminator: bool,
+    require_terminator: bool,
+    terminator: Vec<u8>,
+    max_frame_length: Option<usize>,
+    discard_oversized: bool,
+    // Skipping the rest of an oversized line
+    discarding: bool,
+    // Bytes of the buffer already known not to contain a newline
+    next_index: usize,
+}
+
+impl BytesLinesCodec {
+    /// Creates a new `BytesLinesCodec` without a maximum line length
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether decoded lines have their trailing `\n` or `\r\n` removed
+    pub fn with_strip_terminator(mut self, strip: bool) -> Self {
+        self.strip_terminator = strip;
+        self
+    }
+
+    /// Sets whether a final line without a `\n` at the end of the stream is
+    /// an error, rather than decoded as any other line
+    pub fn with_require_terminator(mut self, require: bool) -> Self {
+        self.require_terminator = require;
+        self
+    }
+
+    /// Sets a terminator, such as `\n` or `\r\n`, appended to each line when
+    /// encoding
+    pub fn with_terminator(mut self, terminator: impl Into<Vec<u8>>) -> Self {
+        self.terminator = terminator.into();
+        self
+    }
+
+    /// Sets the maximum length of a line, excluding its `\n` or `\r\n`
+    ///
+    /// Decoding a longer line returns a [`FrameTooLarge`] error, as soon as
+    /// more bytes than this are buffered without a newline.
+    pub fn with_max_frame_length(mut self, max: usize) -> Self {
+        self.max_frame_length = Some(max);
+        self
+    }
+
+    /// Sets whether oversized lines are skipped up to the next newline after
+    /// returning the error, so decoding can carry on with the next line
+    pub fn with_discard_oversized(mut self, discard: bool) -> Self {
+        self.discard_oversized = discard;
+        self
+    }
+
+    fn too_large(&mut self, src: &mut BytesMut, len: usize, newline: Option<usize>) -> Error {
+        let max = self.max_frame_length.unwrap_or(0);
+        if self.discard_oversized {
+            match newline {
+                Some(pos) => src.advance(pos + 1),
+                None => {
+                    src.clear();
+                    self.discarding = true;
+                }
+            }
+            self.next_index = 0;
+        }
+        FrameTooLarge { len, max }.into()
+    }
+
+    fn encode_line(&mut self, line: &[u8], dst: &mut BytesMut) {
+        dst.reserve(line.len() + self.terminator.len());
+        dst.put(line);
+        dst.put(&self.terminator[..]);
+    }
+}
+
+impl Encoder for BytesLinesCodec {
+    type Item = Bytes;
+    type Error = Error;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode_line(&item, dst);
+        Ok(())
+    }
+}
+
+impl Decoder for BytesLinesCodec {
+    type Item = Bytes;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if self.discarding {
+            match memchr(b'\n', src) {
+                Some(pos) => {
+                    src.advance(pos + 1);
+                    self.discarding = false;
+                }
+                None => {
+                    src.clear();
+                    return Ok(None);
+                }
+            }
+        }
+
+        // Only look at bytes added since the last call, unless the buffer
+        // was replaced by a shorter one, which is searched from the start
+        if self.next_index > src.len() {
+            self.next_index = 0;
+        }
+        let newline = memchr(b'\n', &src[self.next_index..]).map(|i| self.next_index + i);
+
+        match newline {
             Some(pos) => {
-                let buf = src.split_to(pos + 1);
-                String::from_utf8(buf.to_vec())
-                    .map(Some)
-                    .map_err(|e| Error::new(ErrorKind::InvalidData, e))
+                self.next_index = 0;
+                if let Some(max) = self.max_frame_length {
+                    let len = if src[..pos].ends_with(b"\r") {
+                        pos - 1
+                    } else {
+                        pos
+                    };
+                    if len > max {
+                        return Err(self.too_large(src, len, Some(pos)));
+                    }
+                }
+                let mut buf = src.split_to(pos + 1);
+                if self.strip_terminator {
+                    buf.truncate(pos);
+                    if buf.ends_with(b"\r") {
+                        buf.truncate(pos - 1);
+                    }
+                }
+                Ok(Some(buf.freeze()))
+            }
+            None => {
+                self.next_index = src.len();
+                // A trailing `\r` may be the start of a `\r\n`
+                let len = if src.ends_with(b"\r") {
+                    src.len() - 1
+                } else {
+                    src.len()
+                };
+                match self.max_frame_length {
+                    Some(max) if len > max => Err(self.too_large(src, len, None)),
+                    _ => Ok(None),
+                }
             }
-            _ => Ok(None),
         }
     }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.decode(src)? {
+            Some(line) => Ok(Some(line)),
+            None if src.is_empty() || self.require_terminator => Ok(None),
+            None => {
+                self.next_index = 0;
+                let mut buf = src.split_to(src.len());
+                if self.strip_terminator && buf.ends_with(b"\r") {
+                    buf.truncate(buf.len() - 1);
+                }
+                Ok(Some(buf.freeze()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_strips_lf_and_crlf() {
+        let mut codec = LinesCodec::new().with_strip_terminator(true);
+        let mut src = BytesMut::from("one\r\ntwo\n\r\n");
+
+        assert_eq!(codec.decode(&mut src).unwrap(), Some("one".to_owned()));
+        assert_eq!(codec.decode(&mut src).unwrap(), Some("two".to_owned()));
+        assert_eq!(codec.decode(&mut src).unwrap(), Some("".to_owned()));
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+    }
+
+    #[test]
+    fn it_resumes_scanning_where_it_stopped() {
+        let mut codec = LinesCodec::new();
+        let mut src = BytesMut::from("hel");
+
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+        assert_eq!(codec.inner.next_index, 3);
+
+        src.extend_from_slice(b"lo\nworld");
+        assert_eq!(codec.decode(&mut src).unwrap(), Some("hello\n".to_owned()));
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+        assert_eq!(codec.inner.next_index, 5);
+    }
+
+    #[test]
+    fn it_accepts_a_shorter_buffer() {
+        let mut codec = LinesCodec::new();
+
+        assert_eq!(codec.decode(&mut BytesMut::from("abcdef")).unwrap(), None);
+        let mut src = BytesMut::from("x\n");
+        assert_eq!(codec.decode(&mut src).unwrap(), Some("x\n".to_owned()));
+    }
+
+    #[test]
+    fn it_replaces_invalid_utf8_when_lossy() {
+        let mut strict = LinesCodec::new();
+        let mut lossy = LinesCodec::new().with_lossy_utf8(true);
+
+        let mut src = BytesMut::from(&b"caf\xe9\n"[..]);
+        assert!(strict.decode(&mut src.clone()).is_err());
+        assert_eq!(
+            lossy.decode(&mut src).unwrap(),
+            Some("caf\u{fffd}\n".to_owned())
+        );
+    }
+
+    #[test]
+    fn it_splits_lines_without_copying() {
+        let mut codec = BytesLinesCodec::new();
+        let mut src = BytesMut::from("one\ntwo\n");
+        let ptr = src.as_ptr();
+
+        let line = codec.decode(&mut src).unwrap().unwrap();
+        assert_eq!(line, Bytes::from("one\n"));
+        assert_eq!(line.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn it_appends_the_terminator() {
+        let mut codec = LinesCodec::new().with_terminator("\n");
+        let mut dst = BytesMut::new();
+
+        codec.encode("one".to_owned(), &mut dst).unwrap();
+        codec.encode("two".to_owned(), &mut dst).unwrap();
+        assert_eq!(&dst[..], b"one\ntwo\n");
+    }
+
+    #[test]
+    fn it_discards_long_lines_until_newline() {
+        let mut codec = LinesCodec::new()
+            .with_max_frame_length(3)
+            .with_discard_oversized(true);
+        let mut src = BytesMut::from("abcd");
+
+        let err = codec.decode(&mut src).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(src.is_empty());
+
+        src.extend_from_slice(b"ef\nok\n");
+        assert_eq!(codec.decode(&mut src).unwrap(), Some("ok\n".to_owned()));
+    }
+
+    #[test]
+    fn it_leaves_a_trailing_cr_out_of_the_length() {
+        let mut codec = LinesCodec::new().with_max_frame_length(3);
+        let mut src = BytesMut::from("abc\r");
+
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+        src.extend_from_slice(b"\n");
+        assert_eq!(codec.decode(&mut src).unwrap(), Some("abc\r\n".to_owned()));
+    }
+
+    #[test]
+    fn it_errors_on_long_lines_without_discard() {
+        let mut codec = LinesCodec::new().with_max_frame_length(3);
+        let mut src = BytesMut::from("abcd\nok\n");
+
+        assert!(codec.decode(&mut src).is_err());
+        assert_eq!(src.len(), 8);
+    }
 }
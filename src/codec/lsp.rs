@@ -0,0 +1,507 @@
+use std::cmp;
+use std::io::Error as IoError;
+use std::marker::PhantomData;
+
+use super::FrameTooLarge;
+use crate::{Decoder, Encoder};
+use bytes::{Buf, BufMut, BytesMut};
+use memchr::memmem;
+
+use serde::{Deserialize, Serialize};
+
+// A header block longer than this is rejected rather than buffered
+const MAX_HEADER_LENGTH: usize = 8 * 1024;
+
+const HEADER_END: &[u8] = b"\r\n\r\n";
+
+/// A codec for the base protocol of the Language Server Protocol and the
+/// Debug Adapter Protocol, where each JSON body follows a header block
+/// Enc is the type to encode, Dec is the type to decode
+///
+/// ```text
+/// Content-Length: 52\r\n
+/// \r\n
+/// {"jsonrpc":"2.0","method":"initialized","params":{}}
+/// ```
+///
+/// `Content-Length` is required. A `Content-Type` header is optional, but
+/// its charset must be UTF-8. Other headers are ignored.
+/// ```
+/// # futures::executor::block_on(async move {
+/// use futures::{SinkExt, TryStreamExt};
+/// use futures::io::Cursor;
+/// use futures_codec::{Framed, LspCodec};
+/// use serde_json::{json, Value};
+///
+/// let mut framed = Framed::new(Cursor::new(Vec::new()), LspCodec::<Value, Value>::new());
+/// framed.send(json!({"id": 1})).await.unwrap();
+///
+/// let (cur, codec) = framed.release();
+/// assert_eq!(cur.get_ref(), b"Content-Length: 8\r\n\r\n{\"id\":1}");
+///
+/// let mut framed = Framed::new(Cursor::new(cur.into_inner()), codec);
+/// assert_eq!(framed.try_next().await.unwrap(), Some(json!({"id": 1})));
+/// # });
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct LspCodec<Enc, Dec> {
+    enc: PhantomData<Enc>,
+    dec: PhantomData<Dec>,
+    content_type: bool,
+    max_frame_length: Option<usize>,
+    discard_oversized: bool,
+    // Body bytes left to skip from an oversized message
+    discarding: usize,
+    // Length of the body once its header block is read
+    body_length: Option<usize>,
+    // Where to resume looking for the end of the header block
+    next_index: usize,
+}
+
+/// LSP Codec error enumeration
+#[derive(Debug)]
+pub enum LspCodecError {
+    /// IO error
+    Io(IoError),
+    /// JSON error
+    Json(serde_json::Error),
+    /// A header block was malformed, it was skipped along with what it
+    /// could be parsed from
+    InvalidHeader(String),
+    /// A body exceeded the maximum frame length
+    FrameTooLarge(FrameTooLarge),
+}
+
+impl std::fmt::Display for LspCodecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LspCodecError::Io(e) => write!(f, "I/O error: {}", e),
+            LspCodecError::Json(e) => write!(f, "JSON error: {}", e),
+            LspCodecError::InvalidHeader(e) => write!(f, "invalid header: {}", e),
+            LspCodecError::FrameTooLarge(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for LspCodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LspCodecError::Io(ref e) => Some(e),
+            LspCodecError::Json(ref e) => Some(e),
+            LspCodecError::InvalidHeader(_) => None,
+            LspCodecError::FrameTooLarge(ref e) => Some(e),
+        }
+    }
+}
+
+impl From<IoError> for LspCodecError {
+    fn from(e: IoError) -> LspCodecError {
+        LspCodecError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for LspCodecError {
+    fn from(e: serde_json::Error) -> LspCodecError {
+        LspCodecError::Json(e)
+    }
+}
+
+impl From<FrameTooLarge> for LspCodecError {
+    fn from(e: FrameTooLarge) -> LspCodecError {
+        LspCodecError::FrameTooLarge(e)
+    }
+}
+
+impl<Enc, Dec> LspCodec<Enc, Dec>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+{
+    /// Creates a new `LspCodec` with the associated types
+    pub fn new() -> LspCodec<Enc, Dec> {
+        LspCodec {
+            enc: PhantomData,
+            dec: PhantomData,
+            content_type: false,
+            max_frame_length: None,
+            discard_oversized: false,
+            discarding: 0,
+            body_length: None,
+            next_index: 0,
+        }
+    }
+
+    /// Sets whether encoded messages carry a `Content-Type` header
+    ///
+    /// The header is `application/vscode-jsonrpc; charset=utf-8`, which is
+    /// also what a message without one is assumed to be.
+    pub fn with_content_type(mut self, content_type: bool) -> Self {
+        self.content_type = content_type;
+        self
+    }
+
+    /// Sets the maximum length of a body
+    ///
+    /// Decoding a message with a larger `Content-Length` returns
+    /// `LspCodecError::FrameTooLarge`, as does encoding a larger item.
+    pub fn with_max_frame_length(mut self, max: usize) -> Self {
+        self.max_frame_length = Some(max);
+        self
+    }
+
+    /// Sets whether oversized bodies are skipped after returning the error,
+    /// so decoding can carry on with the next message
+    ///
+    /// Otherwise an oversized message is left in the buffer, and decoding
+    /// returns the same error again.
+    pub fn with_discard_oversized(mut self, discard: bool) -> Self {
+        self.discard_oversized = discard;
+        self
+    }
+
+    fn decode_header(&mut self, buf: &mut BytesMut) -> Result<Option<usize>, LspCodecError> {
+        if self.next_index > buf.len() {
+            self.next_index = 0;
+        }
+        let end = match memmem::find(&buf[self.next_index..], HEADER_END) {
+            Some(i) => self.next_index + i,
+            None if buf.len() > MAX_HEADER_LENGTH => {
+                buf.clear();
+                self.next_index = 0;
+                return Err(LspCodecError::InvalidHeader(format!(
+                    "header block is longer than {} bytes",
+                    MAX_HEADER_LENGTH
+                )));
+            }
+            None => {
+                // The terminator may have started in the last bytes
+                self.next_index = buf.len().saturating_sub(HEADER_END.len() - 1);
+                return Ok(None);
+            }
+        };
+        self.next_index = 0;
+
+        let header_len = end + HEADER_END.len();
+        let len = match parse_header(&buf[..end]) {
+            Ok(len) => len,
+            Err((e, len)) => {
+                // Skip the body of a bad message, so the next one can be read
+                buf.advance(header_len);
+                if let Some(len) = len {
+                    self.skip(buf, len);
+                }
+                return Err(LspCodecError::InvalidHeader(e));
+            }
+        };
+
+        // An oversized message is left whole in the buffer unless discarding
+        if let Some(max) = self.max_frame_length {
+            if len > max {
+                if self.discard_oversized {
+                    buf.advance(header_len);
+                    self.skip(buf, len);
+                }
+                return Err(FrameTooLarge { len, max }.into());
+            }
+        }
+        buf.advance(header_len);
+        Ok(Some(len))
+    }
+
+    // Skips a body of `len` bytes, part of which may not be buffered yet
+    fn skip(&mut self, buf: &mut BytesMut, len: usize) {
+        let n = cmp::min(len, buf.len());
+        buf.advance(n);
+        self.discarding = len - n;
+    }
+}
+
+// Reads the header block, without its final empty line, and returns the
+// content length. Errors come with the content length if it could still be
+// read, so the body can be skipped.
+fn parse_header(header: &[u8]) -> Result<usize, (String, Option<usize>)> {
+    let header = std::str::from_utf8(header)
+        .map_err(|_| ("header block is not valid UTF-8".to_owned(), None))?;
+
+    let mut content_length = None;
+    let mut error = None;
+    for line in header.split("\r\n") {
+        if let Err(e) = parse_header_line(line, &mut content_length) {
+            error.get_or_insert(e);
+        }
+    }
+    match (error, content_length) {
+        (Some(e), len) => Err((e, len)),
+        (None, Some(len)) => Ok(len),
+        (None, None) => Err(("missing Content-Length".to_owned(), None)),
+    }
+}
+
+fn parse_header_line(line: &str, content_length: &mut Option<usize>) -> Result<(), String> {
+    let mut parts = line.splitn(2, ':');
+    let (name, value) = match (parts.next(), parts.next()) {
+        (Some(name), Some(value)) => (name.trim(), value.trim()),
+        _ => return Err(format!("malformed header line {:?}", line)),
+    };
+
+    if name.eq_ignore_ascii_case("Content-Length") {
+        let len = value
+            .parse()
+            .map_err(|_| format!("invalid Content-Length {:?}", value))?;
+        *content_length = Some(len);
+    } else if name.eq_ignore_ascii_case("Content-Type") {
+        let charset = value
+            .split(';')
+            .skip(1)
+            .filter_map(|param| {
+                let mut parts = param.splitn(2, '=');
+                match (parts.next(), parts.next()) {
+                    (Some(key), Some(value)) if key.trim().eq_ignore_ascii_case("charset") => {
+                        Some(value.trim().trim_matches('"'))
+                    }
+                    _ => None,
+                }
+            })
+            .next();
+        match charset {
+            // "utf8" is still accepted for backwards compatibility
+            Some(charset)
+                if !charset.eq_ignore_ascii_case("utf-8")
+                    && !charset.eq_ignore_ascii_case("utf8") =>
+            {
+                return Err(format!("unsupported charset {:?}", charset));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+impl<Enc, Dec> Clone for LspCodec<Enc, Dec>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+{
+    /// Clone creates a new instance of the `LspCodec` with the same configuration
+    fn clone(&self) -> LspCodec<Enc, Dec> {
+        LspCodec {
+            enc: PhantomData,
+            dec: PhantomData,
+            content_type: self.content_type,
+            max_frame_length: self.max_frame_length,
+            discard_oversized: self.discard_oversized,
+            discarding: self.discarding,
+            body_length: self.body_length,
+            next_index: self.next_index,
+        }
+    }
+}
+
+/// Decoder impl parses a header block, then the json body it announces
+impl<Enc, Dec> Decoder for LspCodec<Enc, Dec>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+{
+    type Item = Dec;
+    type Error = LspCodecError;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        // Skip what remains of an oversized body
+        if self.discarding > 0 {
+            let n = cmp::min(self.discarding, buf.len());
+            buf.advance(n);
+            self.discarding -= n;
+            if self.discarding > 0 {
+                return Ok(None);
+            }
+        }
+
+        let len = match self.body_length {
+            Some(len) => len,
+            None => match self.decode_header(buf)? {
+                Some(len) => len,
+                None => return Ok(None),
+            },
+        };
+
+        // The buffer isn't reserved up front, as the length comes from the peer
+        if buf.len() < len {
+            self.body_length = Some(len);
+            return Ok(None);
+        }
+        self.body_length = None;
+
+        // The body is consumed even if it fails to parse, so the next
+        // message can still be read
+        let body = buf.split_to(len);
+        Ok(Some(serde_json::from_slice(&body)?))
+    }
+}
+
+/// Encoder impl writes the header block, then the json body
+impl<Enc, Dec> Encoder for LspCodec<Enc, Dec>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+{
+    type Item = Enc;
+    type Error = LspCodecError;
+
+    fn encode(&mut self, data: Self::Item, buf: &mut BytesMut) -> Result<(), Self::Error> {
+        // The length has to be known before the body is written
+        let body = serde_json::to_vec(&data)?;
+
+        if let Some(max) = self.max_frame_length {
+            if body.len() > max {
+                let len = body.len();
+                return Err(FrameTooLarge { len, max }.into());
+            }
+        }
+
+        let header = format!("Content-Length: {}\r\n", body.len());
+        let content_type = if self.content_type {
+            &b"Content-Type: application/vscode-jsonrpc; charset=utf-8\r\n"[..]
+        } else {
+            &b""[..]
+        };
+        buf.reserve(header.len() + content_type.len() + 2 + body.len());
+        buf.put_slice(header.as_bytes());
+        buf.put_slice(content_type);
+        buf.put_slice(b"\r\n");
+        buf.put_slice(&body);
+
+        Ok(())
+    }
+}
+
+impl<Enc, Dec> Default for LspCodec<Enc, Dec>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::BytesMut;
+    use serde::{Deserialize, Serialize};
+
+    use super::{LspCodec, LspCodecError};
+    use crate::{Decoder, Encoder};
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct TestStruct {
+        pub name: String,
+        pub data: u16,
+    }
+
+    #[test]
+    fn lsp_codec_encode_decode() {
+        let mut codec = LspCodec::<TestStruct, TestStruct>::new().with_content_type(true);
+        let mut buff = BytesMut::new();
+
+        let item = TestStruct {
+            name: "Test name".to_owned(),
+            data: 16,
+        };
+        codec.encode(item.clone(), &mut buff).unwrap();
+        assert!(buff.starts_with(b"Content-Length: 30\r\nContent-Type: "));
+
+        // Feed the message a byte at a time
+        let mut input = buff.split();
+        for _ in 0..input.len() - 1 {
+            buff.extend_from_slice(&input.split_to(1));
+            assert_eq!(codec.decode(&mut buff).unwrap(), None);
+        }
+        buff.extend_from_slice(&input);
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(item));
+        assert_eq!(buff.len(), 0);
+    }
+
+    #[test]
+    fn lsp_codec_headers() {
+        let mut codec = LspCodec::<u32, u32>::new();
+        let mut buff = BytesMut::from(
+            &b"content-type: application/vscode-jsonrpc; charset=utf8\r\n\
+               CONTENT-LENGTH: 1\r\nX-Other: ignored\r\n\r\n1\
+               Content-Type: text/plain; charset=latin1\r\nContent-Length: 1\r\n\r\n2\
+               Content-Length: 1\r\nBogus\r\n\r\n3\
+               Content-Length: 1\r\n\r\n4"[..],
+        );
+
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(1));
+        match codec.decode(&mut buff) {
+            Err(LspCodecError::InvalidHeader(e)) => assert_eq!(e, "unsupported charset \"latin1\""),
+            res => panic!("unexpected result {:?}", res),
+        }
+        match codec.decode(&mut buff) {
+            Err(LspCodecError::InvalidHeader(e)) => {
+                assert_eq!(e, "malformed header line \"Bogus\"")
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(4));
+        assert_eq!(buff.len(), 0);
+    }
+
+    #[test]
+    fn lsp_codec_huge_content_length() {
+        let mut codec = LspCodec::<u32, u32>::new();
+        let mut buff = BytesMut::from(&b"Content-Length: 18446744073709551615\r\n\r\n{}"[..]);
+
+        assert_eq!(codec.decode(&mut buff).unwrap(), None);
+        assert!(buff.capacity() < 1024);
+    }
+
+    #[test]
+    fn lsp_codec_discard_oversized() {
+        let mut codec = LspCodec::<u32, u32>::new()
+            .with_max_frame_length(4)
+            .with_discard_oversized(true);
+        let mut buff = BytesMut::from(&b"Content-Length: 6\r\n\r\n123"[..]);
+
+        assert!(matches!(
+            codec.decode(&mut buff),
+            Err(LspCodecError::FrameTooLarge(_))
+        ));
+        buff.extend_from_slice(b"456Content-Length: 2\r\n\r\n78");
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(78));
+    }
+
+    #[test]
+    fn lsp_codec_keeps_oversized() {
+        let mut codec = LspCodec::<u32, u32>::new().with_max_frame_length(4);
+        let input = &b"Content-Length: 8\r\n\r\n12345678Content-Length: 2\r\n\r\n78"[..];
+        let mut buff = BytesMut::from(input);
+
+        for _ in 0..2 {
+            assert!(matches!(
+                codec.decode(&mut buff),
+                Err(LspCodecError::FrameTooLarge(_))
+            ));
+            assert_eq!(&buff[..], input);
+        }
+    }
+
+    #[cfg(feature = "jsonrpc")]
+    #[test]
+    fn lsp_codec_jsonrpc() {
+        use crate::jsonrpc::{Notification, Packet};
+        use serde_json::json;
+
+        let mut codec = LspCodec::<Packet, Packet>::new();
+        let mut buff = BytesMut::new();
+
+        let packet = Packet::Single(Notification::new("initialized", Some(json!({}))).into());
+        codec.encode(packet.clone(), &mut buff).unwrap();
+        assert_eq!(
+            &buff[..],
+            &b"Content-Length: 52\r\n\r\n{\"jsonrpc\":\"2.0\",\"method\":\"initialized\",\"params\":{}}"[..]
+        );
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(packet));
+    }
+}
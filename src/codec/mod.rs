@@ -1,18 +1,81 @@
+mod any_delimiter;
+pub use self::any_delimiter::AnyDelimiterCodec;
+
 mod bytes;
 pub use self::bytes::BytesCodec;
 
+#[cfg(any(feature = "crc32", feature = "crc32c", feature = "xxhash"))]
+mod checksummed;
+#[cfg(any(feature = "crc32", feature = "crc32c", feature = "xxhash"))]
+pub use self::checksummed::{ChecksumAlgorithm, Checksummed, ChecksummedError};
+
+mod cobs;
+pub use self::cobs::CobsCodec;
+
+#[cfg(any(feature = "deflate", feature = "zstd", feature = "lz4"))]
+mod compressed;
+#[cfg(any(feature = "deflate", feature = "zstd", feature = "lz4"))]
+pub use self::compressed::{Compressed, CompressedError, CompressionAlgorithm};
+
+mod error;
+pub use self::error::FrameTooLarge;
+
 mod length;
 pub use self::length::LengthCodec;
 
+mod length_delimited;
+pub use self::length_delimited::{LengthDelimitedCodec, LengthDelimitedCodecBuilder};
+
+#[cfg(any(feature = "json", feature = "cbor"))]
+mod limits;
+
 mod lines;
-pub use self::lines::LinesCodec;
+pub use self::lines::{BytesLinesCodec, LinesCodec};
+
+mod slip;
+pub use self::slip::SlipCodec;
+
+mod varint;
+pub use self::varint::VarintLengthCodec;
 
 #[cfg(feature = "json")]
 mod json;
 #[cfg(feature = "json")]
-pub use self::json::{JsonCodec, JsonCodecError};
+mod json_scanner;
+#[cfg(feature = "json")]
+mod json_seq;
+#[cfg(feature = "json")]
+pub use self::json::{JsonCodec, JsonCodecError, RawJsonCodec};
+#[cfg(feature = "json")]
+pub use self::json_seq::JsonSeqCodec;
+#[cfg(feature = "json")]
+mod lsp;
+#[cfg(feature = "json")]
+pub use self::lsp::{LspCodec, LspCodecError};
 
 #[cfg(feature = "cbor")]
 mod cbor;
 #[cfg(feature = "cbor")]
-pub use self::cbor::{CborCodec, CborCodecError};
+mod cbor_scanner;
+#[cfg(feature = "cbor")]
+pub use self::cbor::{CborCodec, CborCodecError, RawCborCodec};
+
+#[cfg(feature = "msgpack")]
+mod msgpack;
+#[cfg(feature = "msgpack")]
+pub use self::msgpack::{MsgPackCodec, MsgPackCodecError};
+
+#[cfg(feature = "bincode")]
+mod bincode;
+#[cfg(feature = "bincode")]
+pub use self::bincode::{BincodeCodec, BincodeCodecError};
+
+#[cfg(feature = "postcard")]
+mod postcard;
+#[cfg(feature = "postcard")]
+pub use self::postcard::{PostcardCodec, PostcardCodecError};
+
+#[cfg(feature = "prost")]
+mod protobuf;
+#[cfg(feature = "prost")]
+pub use self::protobuf::{ProtobufCodec, ProtobufCodecError};
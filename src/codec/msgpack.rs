@@ -0,0 +1,391 @@
+use std::cmp;
+use std::io::{Cursor, Error as IoError, ErrorKind};
+use std::marker::PhantomData;
+
+use super::FrameTooLarge;
+use crate::{Decoder, Encoder};
+use bytes::buf::BufMutExt;
+use bytes::{Buf, BytesMut};
+
+use rmp_serde::{decode, encode};
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+
+/// A codec for MessagePack encoding and decoding using rmp-serde
+/// Enc is the type to encode, Dec is the type to decode
+///
+/// Structs are encoded as arrays of their fields by default, as rmp-serde
+/// does. Use [`with_struct_map`](#method.with_struct_map) to encode them as
+/// maps keyed by field name instead. Both forms are accepted when decoding.
+/// ```
+/// # use futures::{executor, SinkExt, TryStreamExt};
+/// # use futures::io::Cursor;
+/// use serde::{Serialize, Deserialize};
+/// use futures_codec::{MsgPackCodec, Framed};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Something {
+///     pub data: u16,
+/// }
+///
+/// async move {
+///     # let mut buf = vec![];
+///     # let stream = Cursor::new(&mut buf);
+///     // let stream = ...
+///     let codec = MsgPackCodec::<Something, Something>::new();
+///     let mut framed = Framed::new(stream, codec);
+///
+///     while let Some(s) = framed.try_next().await.unwrap() {
+///         println!("{:?}", s.data);
+///     }
+/// };
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct MsgPackCodec<Enc, Dec> {
+    enc: PhantomData<Enc>,
+    dec: PhantomData<Dec>,
+    struct_map: bool,
+    max_frame_length: Option<usize>,
+}
+
+/// MessagePack Codec error enumeration
+#[derive(Debug)]
+pub enum MsgPackCodecError {
+    /// IO error
+    Io(IoError),
+    /// MessagePack encoding error
+    Encode(encode::Error),
+    /// MessagePack decoding error
+    Decode(decode::Error),
+    /// A value exceeded the maximum frame length
+    FrameTooLarge(FrameTooLarge),
+}
+
+impl std::fmt::Display for MsgPackCodecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MsgPackCodecError::Io(e) => write!(f, "I/O error: {}", e),
+            MsgPackCodecError::Encode(e) => write!(f, "MessagePack encoding error: {}", e),
+            MsgPackCodecError::Decode(e) => write!(f, "MessagePack decoding error: {}", e),
+            MsgPackCodecError::FrameTooLarge(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for MsgPackCodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MsgPackCodecError::Io(ref e) => Some(e),
+            MsgPackCodecError::Encode(ref e) => Some(e),
+            MsgPackCodecError::Decode(ref e) => Some(e),
+            MsgPackCodecError::FrameTooLarge(ref e) => Some(e),
+        }
+    }
+}
+
+impl From<IoError> for MsgPackCodecError {
+    fn from(e: IoError) -> MsgPackCodecError {
+        MsgPackCodecError::Io(e)
+    }
+}
+
+impl From<encode::Error> for MsgPackCodecError {
+    fn from(e: encode::Error) -> MsgPackCodecError {
+        MsgPackCodecError::Encode(e)
+    }
+}
+
+impl From<decode::Error> for MsgPackCodecError {
+    fn from(e: decode::Error) -> MsgPackCodecError {
+        MsgPackCodecError::Decode(e)
+    }
+}
+
+impl From<FrameTooLarge> for MsgPackCodecError {
+    fn from(e: FrameTooLarge) -> MsgPackCodecError {
+        MsgPackCodecError::FrameTooLarge(e)
+    }
+}
+
+impl<Enc, Dec> MsgPackCodec<Enc, Dec>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+{
+    /// Creates a new `MsgPackCodec` with the associated types
+    pub fn new() -> MsgPackCodec<Enc, Dec> {
+        MsgPackCodec {
+            enc: PhantomData,
+            dec: PhantomData,
+            struct_map: false,
+            max_frame_length: None,
+        }
+    }
+
+    /// Sets whether structs are encoded as maps keyed by field name, rather
+    /// than as arrays of their fields
+    ///
+    /// Maps are larger but can be read without knowing the field order,
+    /// which matters when talking to implementations in other languages.
+    pub fn with_struct_map(mut self, struct_map: bool) -> Self {
+        self.struct_map = struct_map;
+        self
+    }
+
+    /// Sets the maximum length of an encoded value
+    ///
+    /// Decoding a larger value returns `MsgPackCodecError::FrameTooLarge`,
+    /// as soon as more bytes than this are buffered for an incomplete value.
+    pub fn with_max_frame_length(mut self, max: usize) -> Self {
+        self.max_frame_length = Some(max);
+        self
+    }
+}
+
+impl<Enc, Dec> Clone for MsgPackCodec<Enc, Dec>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+{
+    /// Clone creates a new instance of the `MsgPackCodec` with the same configuration
+    fn clone(&self) -> MsgPackCodec<Enc, Dec> {
+        MsgPackCodec {
+            enc: PhantomData,
+            dec: PhantomData,
+            struct_map: self.struct_map,
+            max_frame_length: self.max_frame_length,
+        }
+    }
+}
+
+// Whether decoding failed only because the value is not fully buffered yet
+fn is_eof(e: &decode::Error) -> bool {
+    match e {
+        decode::Error::InvalidMarkerRead(e) | decode::Error::InvalidDataRead(e) => {
+            e.kind() == ErrorKind::UnexpectedEof
+        }
+        _ => false,
+    }
+}
+
+/// Decoder impl parses msgpack values from bytes
+impl<Enc, Dec> Decoder for MsgPackCodec<Enc, Dec>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+{
+    type Item = Dec;
+    type Error = MsgPackCodecError;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if buf.is_empty() {
+            return Ok(None);
+        }
+
+        // Build deserializer
+        let mut de = rmp_serde::Deserializer::new(Cursor::new(&buf[..]));
+
+        // Attempt deserialization
+        let res: Result<Dec, _> = serde::de::Deserialize::deserialize(&mut de);
+
+        // If we ran out before parsing, return none and try again later
+        let mut res = match res {
+            Ok(v) => Ok(Some(v)),
+            Err(ref e) if is_eof(e) => Ok(None),
+            Err(e) => Err(MsgPackCodecError::from(e)),
+        };
+
+        // Update offset from deserializer
+        let mut offset = de.position() as usize;
+
+        // The deserializer may stop partway through a value of the wrong
+        // type, so measure the whole value to skip it
+        if res.is_err() {
+            let mut de = rmp_serde::Deserializer::new(Cursor::new(&buf[..]));
+            match IgnoredAny::deserialize(&mut de) {
+                Ok(_) => offset = de.position() as usize,
+                Err(ref e) if is_eof(e) => res = Ok(None),
+                // The data itself is malformed, skip past the bad part
+                Err(_) => offset = cmp::max(de.position() as usize, 1),
+            }
+        }
+
+        if let Some(max) = self.max_frame_length {
+            match res {
+                Ok(Some(_)) if offset > max => {
+                    buf.advance(offset);
+                    return Err(FrameTooLarge { len: offset, max }.into());
+                }
+                Ok(None) if buf.len() > max => {
+                    let len = buf.len();
+                    return Err(FrameTooLarge { len, max }.into());
+                }
+                _ => {}
+            }
+        }
+
+        // Advance buffer, unless the value is incomplete
+        if let Ok(Some(_)) | Err(_) = res {
+            buf.advance(offset);
+        }
+
+        res
+    }
+}
+
+/// Encoder impl encodes object streams to bytes
+impl<Enc, Dec> Encoder for MsgPackCodec<Enc, Dec>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+{
+    type Item = Enc;
+    type Error = MsgPackCodecError;
+
+    fn encode(&mut self, data: Self::Item, buf: &mut BytesMut) -> Result<(), Self::Error> {
+        // Encode msgpack straight into the buffer
+        let start = buf.len();
+        let mut ser = rmp_serde::Serializer::new((&mut *buf).writer());
+        let res = if self.struct_map {
+            data.serialize(&mut ser.with_struct_map())
+        } else {
+            data.serialize(&mut ser)
+        };
+        if let Err(e) = res {
+            // Don't leave part of the value in the buffer
+            buf.truncate(start);
+            return Err(e.into());
+        }
+
+        Ok(())
+    }
+}
+
+impl<Enc, Dec> Default for MsgPackCodec<Enc, Dec>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::BytesMut;
+    use serde::ser::{Error, SerializeSeq, Serializer};
+    use serde::{Deserialize, Serialize};
+
+    use super::{MsgPackCodec, MsgPackCodecError};
+    use crate::{Decoder, Encoder};
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct TestStruct {
+        pub name: String,
+        pub data: u16,
+    }
+
+    // Writes the start of a sequence before failing
+    struct Unserializable;
+
+    impl Serialize for Unserializable {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut seq = serializer.serialize_seq(Some(2))?;
+            seq.serialize_element(&1)?;
+            Err(S::Error::custom("unserializable"))
+        }
+    }
+
+    #[test]
+    fn msgpack_codec_encode_decode() {
+        let mut codec = MsgPackCodec::<TestStruct, TestStruct>::new();
+        let mut buff = BytesMut::new();
+
+        let item1 = TestStruct {
+            name: "Test name".to_owned(),
+            data: 16,
+        };
+        codec.encode(item1.clone(), &mut buff).unwrap();
+
+        let item2 = codec.decode(&mut buff).unwrap().unwrap();
+        assert_eq!(item1, item2);
+
+        assert_eq!(codec.decode(&mut buff).unwrap(), None);
+
+        assert_eq!(buff.len(), 0);
+    }
+
+    #[test]
+    fn msgpack_codec_encode_error() {
+        let mut codec = MsgPackCodec::<Unserializable, ()>::new();
+        let mut buff = BytesMut::from(&b"\xc0"[..]);
+
+        assert!(matches!(
+            codec.encode(Unserializable, &mut buff),
+            Err(MsgPackCodecError::Encode(_))
+        ));
+        assert_eq!(&buff[..], b"\xc0");
+    }
+
+    #[test]
+    fn msgpack_codec_partial_decode() {
+        let mut codec = MsgPackCodec::<TestStruct, TestStruct>::new().with_struct_map(true);
+        let mut buff = BytesMut::new();
+
+        let item = TestStruct {
+            name: "Test name".to_owned(),
+            data: 34,
+        };
+        codec.encode(item.clone(), &mut buff).unwrap();
+        assert!(buff.windows(4).any(|w| w == b"data"));
+
+        // Every prefix is incomplete, not an error
+        let mut input = buff.split();
+        for _ in 0..input.len() - 1 {
+            buff.extend_from_slice(&input.split_to(1));
+            assert_eq!(codec.decode(&mut buff).unwrap(), None);
+        }
+        buff.extend_from_slice(&input);
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(item));
+        assert_eq!(buff.len(), 0);
+    }
+
+    #[test]
+    fn msgpack_codec_skips_mismatched_values() {
+        let mut codec = MsgPackCodec::<u8, u8>::new();
+        // [7, 8], then 9
+        let mut buff = BytesMut::from(&b"\x92\x07"[..]);
+
+        // The rest of the array is awaited before the error
+        assert_eq!(codec.decode(&mut buff).unwrap(), None);
+        buff.extend_from_slice(b"\x08\x09");
+        assert!(matches!(
+            codec.decode(&mut buff),
+            Err(MsgPackCodecError::Decode(_))
+        ));
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(9));
+        assert_eq!(buff.len(), 0);
+    }
+
+    #[test]
+    fn msgpack_codec_errors() {
+        let mut codec = MsgPackCodec::<u8, u8>::new().with_max_frame_length(4);
+        let mut buff = BytesMut::from(&b"\xc1\x01"[..]);
+
+        assert!(matches!(
+            codec.decode(&mut buff),
+            Err(MsgPackCodecError::Decode(_))
+        ));
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(1));
+
+        let mut codec = MsgPackCodec::<String, String>::new().with_max_frame_length(4);
+        buff.extend_from_slice(b"\xa5ab");
+        assert_eq!(codec.decode(&mut buff).unwrap(), None);
+        buff.extend_from_slice(b"cde");
+        assert!(matches!(
+            codec.decode(&mut buff),
+            Err(MsgPackCodecError::FrameTooLarge(_))
+        ));
+    }
+}
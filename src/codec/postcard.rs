@@ -0,0 +1,336 @@
+use std::io::Error as IoError;
+use std::marker::PhantomData;
+
+use super::FrameTooLarge;
+use crate::{Decoder, Encoder};
+use bytes::{Buf, BytesMut};
+use memchr::memchr;
+
+use serde::{Deserialize, Serialize};
+
+/// A codec for postcard encoding and decoding using the postcard crate
+/// Enc is the type to encode, Dec is the type to decode
+///
+/// Each value is COBS encoded and followed by a `0x00` delimiter, as
+/// `postcard::to_stdvec_cobs` does. Since the delimiter can't appear inside
+/// a frame, decoding recovers at the next frame after a corrupt one.
+/// ```
+/// # use futures::{executor, SinkExt, TryStreamExt};
+/// # use futures::io::Cursor;
+/// use serde::{Serialize, Deserialize};
+/// use futures_codec::{PostcardCodec, Framed};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Something {
+///     pub data: u16,
+/// }
+///
+/// async move {
+///     # let mut buf = vec![];
+///     # let stream = Cursor::new(&mut buf);
+///     // let stream = ...
+///     let codec = PostcardCodec::<Something, Something>::new();
+///     let mut framed = Framed::new(stream, codec);
+///
+///     while let Some(s) = framed.try_next().await.unwrap() {
+///         println!("{:?}", s.data);
+///     }
+/// };
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct PostcardCodec<Enc, Dec> {
+    enc: PhantomData<Enc>,
+    dec: PhantomData<Dec>,
+    max_frame_length: Option<usize>,
+    discard_oversized: bool,
+    // Whether the rest of an oversized frame is being skipped
+    discarding: bool,
+    // Bytes already searched for the delimiter
+    next_index: usize,
+}
+
+/// Postcard Codec error enumeration
+#[derive(Debug)]
+pub enum PostcardCodecError {
+    /// IO error
+    Io(IoError),
+    /// Postcard error, including frames that aren't valid COBS
+    Postcard(postcard::Error),
+    /// A frame exceeded the maximum frame length
+    FrameTooLarge(FrameTooLarge),
+}
+
+impl std::fmt::Display for PostcardCodecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PostcardCodecError::Io(e) => write!(f, "I/O error: {}", e),
+            PostcardCodecError::Postcard(e) => write!(f, "Postcard error: {}", e),
+            PostcardCodecError::FrameTooLarge(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for PostcardCodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PostcardCodecError::Io(ref e) => Some(e),
+            PostcardCodecError::Postcard(ref e) => Some(e),
+            PostcardCodecError::FrameTooLarge(ref e) => Some(e),
+        }
+    }
+}
+
+impl From<IoError> for PostcardCodecError {
+    fn from(e: IoError) -> PostcardCodecError {
+        PostcardCodecError::Io(e)
+    }
+}
+
+impl From<postcard::Error> for PostcardCodecError {
+    fn from(e: postcard::Error) -> PostcardCodecError {
+        PostcardCodecError::Postcard(e)
+    }
+}
+
+impl From<FrameTooLarge> for PostcardCodecError {
+    fn from(e: FrameTooLarge) -> PostcardCodecError {
+        PostcardCodecError::FrameTooLarge(e)
+    }
+}
+
+impl<Enc, Dec> PostcardCodec<Enc, Dec>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+{
+    /// Creates a new `PostcardCodec` with the associated types
+    pub fn new() -> PostcardCodec<Enc, Dec> {
+        PostcardCodec {
+            enc: PhantomData,
+            dec: PhantomData,
+            max_frame_length: None,
+            discard_oversized: false,
+            discarding: false,
+            next_index: 0,
+        }
+    }
+
+    /// Sets the maximum length of a COBS encoded frame, excluding its
+    /// delimiter
+    ///
+    /// Decoding a larger frame returns `PostcardCodecError::FrameTooLarge`,
+    /// as soon as more bytes than this are buffered without a delimiter, as
+    /// does encoding a larger value.
+    pub fn with_max_frame_length(mut self, max: usize) -> Self {
+        self.max_frame_length = Some(max);
+        self
+    }
+
+    /// Sets whether oversized frames are skipped after returning the error,
+    /// so decoding can carry on with the next frame
+    ///
+    /// Otherwise an oversized frame is left in the buffer, and decoding
+    /// returns the same error again.
+    pub fn with_discard_oversized(mut self, discard: bool) -> Self {
+        self.discard_oversized = discard;
+        self
+    }
+}
+
+impl<Enc, Dec> Clone for PostcardCodec<Enc, Dec>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+{
+    /// Clone creates a new instance of the `PostcardCodec` with the same configuration
+    fn clone(&self) -> PostcardCodec<Enc, Dec> {
+        PostcardCodec {
+            enc: PhantomData,
+            dec: PhantomData,
+            max_frame_length: self.max_frame_length,
+            discard_oversized: self.discard_oversized,
+            discarding: false,
+            next_index: 0,
+        }
+    }
+}
+
+/// Decoder impl parses postcard values from COBS frames
+impl<Enc, Dec> Decoder for PostcardCodec<Enc, Dec>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+{
+    type Item = Dec;
+    type Error = PostcardCodecError;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            if self.next_index > buf.len() {
+                self.next_index = 0;
+            }
+            let end = match memchr(0, &buf[self.next_index..]) {
+                Some(i) => self.next_index + i,
+                None => {
+                    self.next_index = buf.len();
+                    if self.discarding {
+                        buf.clear();
+                        self.next_index = 0;
+                        return Ok(None);
+                    }
+                    if let Some(max) = self.max_frame_length {
+                        if buf.len() > max {
+                            let len = buf.len();
+                            if self.discard_oversized {
+                                buf.clear();
+                                self.next_index = 0;
+                                self.discarding = true;
+                            }
+                            return Err(FrameTooLarge { len, max }.into());
+                        }
+                    }
+                    return Ok(None);
+                }
+            };
+            self.next_index = 0;
+
+            if self.discarding {
+                buf.advance(end + 1);
+                self.discarding = false;
+                continue;
+            }
+            if let Some(max) = self.max_frame_length {
+                if end > max {
+                    if self.discard_oversized {
+                        buf.advance(end + 1);
+                    }
+                    return Err(FrameTooLarge { len: end, max }.into());
+                }
+            }
+
+            let mut frame = buf.split_to(end + 1);
+            // Consecutive delimiters are allowed between frames
+            if end == 0 {
+                continue;
+            }
+
+            return Ok(Some(postcard::from_bytes_cobs(&mut frame[..end])?));
+        }
+    }
+}
+
+/// Encoder impl encodes values to COBS frames
+impl<Enc, Dec> Encoder for PostcardCodec<Enc, Dec>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+{
+    type Item = Enc;
+    type Error = PostcardCodecError;
+
+    fn encode(&mut self, data: Self::Item, buf: &mut BytesMut) -> Result<(), Self::Error> {
+        // The delimiter is included in the encoded value
+        let frame = postcard::to_stdvec_cobs(&data)?;
+        if let Some(max) = self.max_frame_length {
+            if frame.len() - 1 > max {
+                let len = frame.len() - 1;
+                return Err(FrameTooLarge { len, max }.into());
+            }
+        }
+
+        buf.extend_from_slice(&frame);
+        Ok(())
+    }
+}
+
+impl<Enc, Dec> Default for PostcardCodec<Enc, Dec>
+where
+    for<'de> Dec: Deserialize<'de> + 'static,
+    for<'de> Enc: Serialize + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::BytesMut;
+    use serde::{Deserialize, Serialize};
+
+    use super::{PostcardCodec, PostcardCodecError};
+    use crate::{Decoder, Encoder};
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct TestStruct {
+        pub name: String,
+        pub data: u16,
+    }
+
+    #[test]
+    fn postcard_codec_encode_decode() {
+        let mut codec = PostcardCodec::<TestStruct, TestStruct>::new();
+        let mut buff = BytesMut::new();
+
+        let item1 = TestStruct {
+            name: "Test name".to_owned(),
+            data: 256,
+        };
+        codec.encode(item1.clone(), &mut buff).unwrap();
+        codec.encode(item1.clone(), &mut buff).unwrap();
+        assert_eq!(buff.iter().filter(|b| **b == 0).count(), 2);
+
+        // Every prefix of the first frame is incomplete, not an error
+        let mut input = buff.split();
+        for _ in 0..input.len() / 2 - 1 {
+            buff.extend_from_slice(&input.split_to(1));
+            assert_eq!(codec.decode(&mut buff).unwrap(), None);
+        }
+        buff.extend_from_slice(&input);
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(item1.clone()));
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(item1));
+        assert_eq!(codec.decode(&mut buff).unwrap(), None);
+        assert_eq!(buff.len(), 0);
+    }
+
+    #[test]
+    fn postcard_codec_errors() {
+        let mut codec = PostcardCodec::<bool, bool>::new()
+            .with_max_frame_length(2)
+            .with_discard_oversized(true);
+        let mut buff = BytesMut::from(&b"\x02\x05\x00\x00\x04abc"[..]);
+
+        assert!(matches!(
+            codec.decode(&mut buff),
+            Err(PostcardCodecError::Postcard(_))
+        ));
+        assert!(matches!(
+            codec.decode(&mut buff),
+            Err(PostcardCodecError::FrameTooLarge(_))
+        ));
+        buff.extend_from_slice(b"\x00\x02\x01\x00");
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(true));
+        assert_eq!(buff.len(), 0);
+
+        let mut codec = PostcardCodec::<String, String>::new().with_max_frame_length(2);
+        assert!(matches!(
+            codec.encode("abc".to_owned(), &mut buff),
+            Err(PostcardCodecError::FrameTooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn postcard_codec_keeps_oversized() {
+        let mut codec = PostcardCodec::<bool, bool>::new().with_max_frame_length(2);
+        let input = &b"\x04abc\x00\x02\x01\x00"[..];
+        let mut buff = BytesMut::from(input);
+
+        for _ in 0..2 {
+            assert!(matches!(
+                codec.decode(&mut buff),
+                Err(PostcardCodecError::FrameTooLarge(_))
+            ));
+            assert_eq!(&buff[..], input);
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use std::io::Error as IoError;
+use std::marker::PhantomData;
+
+use super::{FrameTooLarge, VarintLengthCodec};
+use crate::{Decoder, Encoder};
+use bytes::BytesMut;
+
+use prost::{DecodeError, EncodeError, Message};
+
+/// Maximum message length used unless configured otherwise, the same as
+/// most gRPC implementations
+const DEFAULT_MAX_FRAME_LENGTH: usize = 4 * 1024 * 1024;
+
+/// A codec for protobuf encoding and decoding using prost
+/// Enc is the message type to encode, Dec is the message type to decode
+///
+/// Messages are prefixed with their length as an unsigned LEB128 varint, as
+/// written by `Message::encode_length_delimited` and by the delimited
+/// stream helpers of other protobuf libraries. A message is only decoded
+/// once all of it is buffered.
+///
+/// Messages longer than 4 MiB are rejected unless a different maximum is
+/// set with [`with_max_frame_length`](#method.with_max_frame_length).
+/// ```
+/// # use futures::{executor, SinkExt, TryStreamExt};
+/// # use futures::io::Cursor;
+/// use futures_codec::{ProtobufCodec, Framed};
+///
+/// #[derive(Clone, PartialEq, prost::Message)]
+/// struct Something {
+///     #[prost(uint32, tag = "1")]
+///     pub data: u32,
+/// }
+///
+/// async move {
+///     # let mut buf = vec![];
+///     # let stream = Cursor::new(&mut buf);
+///     // let stream = ...
+///     let codec = ProtobufCodec::<Something, Something>::new();
+///     let mut framed = Framed::new(stream, codec);
+///
+///     while let Some(s) = framed.try_next().await.unwrap() {
+///         println!("{:?}", s.data);
+///     }
+/// };
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct ProtobufCodec<Enc, Dec> {
+    enc: PhantomData<Enc>,
+    dec: PhantomData<Dec>,
+    framing: VarintLengthCodec,
+    max_frame_length: usize,
+}
+
+/// Protobuf Codec error enumeration
+#[derive(Debug)]
+pub enum ProtobufCodecError {
+    /// IO error
+    Io(IoError),
+    /// Protobuf encoding error
+    Encode(EncodeError),
+    /// Protobuf decoding error
+    Decode(DecodeError),
+    /// A message exceeded the maximum frame length
+    FrameTooLarge(FrameTooLarge),
+}
+
+impl std::fmt::Display for ProtobufCodecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtobufCodecError::Io(e) => write!(f, "I/O error: {}", e),
+            ProtobufCodecError::Encode(e) => write!(f, "Protobuf encoding error: {}", e),
+            ProtobufCodecError::Decode(e) => write!(f, "Protobuf decoding error: {}", e),
+            ProtobufCodecError::FrameTooLarge(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ProtobufCodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProtobufCodecError::Io(ref e) => Some(e),
+            ProtobufCodecError::Encode(ref e) => Some(e),
+            ProtobufCodecError::Decode(ref e) => Some(e),
+            ProtobufCodecError::FrameTooLarge(ref e) => Some(e),
+        }
+    }
+}
+
+impl From<IoError> for ProtobufCodecError {
+    fn from(e: IoError) -> ProtobufCodecError {
+        ProtobufCodecError::Io(e)
+    }
+}
+
+impl From<EncodeError> for ProtobufCodecError {
+    fn from(e: EncodeError) -> ProtobufCodecError {
+        ProtobufCodecError::Encode(e)
+    }
+}
+
+impl From<DecodeError> for ProtobufCodecError {
+    fn from(e: DecodeError) -> ProtobufCodecError {
+        ProtobufCodecError::Decode(e)
+    }
+}
+
+impl From<FrameTooLarge> for ProtobufCodecError {
+    fn from(e: FrameTooLarge) -> ProtobufCodecError {
+        ProtobufCodecError::FrameTooLarge(e)
+    }
+}
+
+impl<Enc, Dec> ProtobufCodec<Enc, Dec>
+where
+    Enc: Message,
+    Dec: Message + Default,
+{
+    /// Creates a new `ProtobufCodec` with the associated types
+    pub fn new() -> ProtobufCodec<Enc, Dec> {
+        ProtobufCodec {
+            enc: PhantomData,
+            dec: PhantomData,
+            framing: VarintLengthCodec::new().with_max_frame_length(DEFAULT_MAX_FRAME_LENGTH),
+            max_frame_length: DEFAULT_MAX_FRAME_LENGTH,
+        }
+    }
+
+    /// Sets the maximum length of a message, excluding its header, 4 MiB
+    /// by default
+    ///
+    /// Decoding a longer message returns `ProtobufCodecError::FrameTooLarge`
+    /// as soon as its header is read, without buffering it, as does encoding
+    /// a longer message.
+    pub fn with_max_frame_length(mut self, max: usize) -> Self {
+        self.framing = self.framing.with_max_frame_length(max);
+        self.max_frame_length = max;
+        self
+    }
+
+    /// Sets whether oversized messages are skipped after returning the
+    /// error, so decoding can carry on with the next message
+    pub fn with_discard_oversized(mut self, discard: bool) -> Self {
+        self.framing = self.framing.with_discard_oversized(discard);
+        self
+    }
+}
+
+impl<Enc, Dec> Clone for ProtobufCodec<Enc, Dec>
+where
+    Enc: Message,
+    Dec: Message + Default,
+{
+    /// Clone creates a new instance of the `ProtobufCodec` with the same configuration
+    fn clone(&self) -> ProtobufCodec<Enc, Dec> {
+        ProtobufCodec {
+            enc: PhantomData,
+            dec: PhantomData,
+            framing: self.framing,
+            max_frame_length: self.max_frame_length,
+        }
+    }
+}
+
+fn framing_error(e: IoError) -> ProtobufCodecError {
+    match e.get_ref().and_then(|e| e.downcast_ref::<FrameTooLarge>()) {
+        Some(too_large) => ProtobufCodecError::FrameTooLarge(*too_large),
+        None => e.into(),
+    }
+}
+
+/// Decoder impl parses protobuf messages from length-delimited frames
+impl<Enc, Dec> Decoder for ProtobufCodec<Enc, Dec>
+where
+    Enc: Message,
+    Dec: Message + Default,
+{
+    type Item = Dec;
+    type Error = ProtobufCodecError;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let frame = match self.framing.decode(buf).map_err(framing_error)? {
+            Some(frame) => frame,
+            None => return Ok(None),
+        };
+
+        // The whole frame is consumed even if it fails to decode
+        Ok(Some(Dec::decode(frame)?))
+    }
+}
+
+/// Encoder impl encodes protobuf messages to length-delimited frames
+impl<Enc, Dec> Encoder for ProtobufCodec<Enc, Dec>
+where
+    Enc: Message,
+    Dec: Message + Default,
+{
+    type Item = Enc;
+    type Error = ProtobufCodecError;
+
+    fn encode(&mut self, data: Self::Item, buf: &mut BytesMut) -> Result<(), Self::Error> {
+        let len = data.encoded_len();
+        if len > self.max_frame_length {
+            let max = self.max_frame_length;
+            return Err(FrameTooLarge { len, max }.into());
+        }
+
+        buf.reserve(len + prost::length_delimiter_len(len));
+        data.encode_length_delimited(buf)?;
+        Ok(())
+    }
+}
+
+impl<Enc, Dec> Default for ProtobufCodec<Enc, Dec>
+where
+    Enc: Message,
+    Dec: Message + Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::BytesMut;
+
+    use super::{ProtobufCodec, ProtobufCodecError};
+    use crate::{Decoder, Encoder};
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct TestMessage {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(uint32, tag = "2")]
+        pub data: u32,
+    }
+
+    #[test]
+    fn protobuf_codec_encode_decode() {
+        let mut codec = ProtobufCodec::<TestMessage, TestMessage>::new();
+        let mut buff = BytesMut::new();
+
+        let item1 = TestMessage {
+            name: "Test name".to_owned(),
+            data: 300,
+        };
+        codec.encode(item1.clone(), &mut buff).unwrap();
+        codec.encode(item1.clone(), &mut buff).unwrap();
+
+        // Varint length, then the name field's key and length
+        assert_eq!(&buff[..3], b"\x0e\x0a\x09");
+
+        // Every prefix of the first message is incomplete, not an error
+        let mut input = buff.split();
+        for _ in 0..14 {
+            buff.extend_from_slice(&input.split_to(1));
+            assert_eq!(codec.decode(&mut buff).unwrap(), None);
+        }
+        buff.extend_from_slice(&input);
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(item1.clone()));
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(item1));
+        assert_eq!(codec.decode(&mut buff).unwrap(), None);
+        assert_eq!(buff.len(), 0);
+    }
+
+    #[test]
+    fn protobuf_codec_errors() {
+        let mut codec = ProtobufCodec::<TestMessage, TestMessage>::new()
+            .with_max_frame_length(4)
+            .with_discard_oversized(true);
+        let mut buff = BytesMut::from(&b"\x02\x0a\x09\x05\x0a\x03abc\x02\x10\x07"[..]);
+
+        assert!(matches!(
+            codec.decode(&mut buff),
+            Err(ProtobufCodecError::Decode(_))
+        ));
+        assert!(matches!(
+            codec.decode(&mut buff),
+            Err(ProtobufCodecError::FrameTooLarge(_))
+        ));
+        let item = TestMessage {
+            name: String::new(),
+            data: 7,
+        };
+        assert_eq!(codec.decode(&mut buff).unwrap(), Some(item));
+
+        let item = TestMessage {
+            name: "abc".to_owned(),
+            data: 0,
+        };
+        assert!(matches!(
+            codec.encode(item, &mut buff),
+            Err(ProtobufCodecError::FrameTooLarge(_))
+        ));
+        assert_eq!(buff.len(), 0);
+    }
+}
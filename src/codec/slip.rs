@@ -0,0 +1,279 @@
+use super::FrameTooLarge;
+use crate::{Decoder, Encoder};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use memchr::{memchr, memchr2};
+use std::io::{Error, ErrorKind};
+
+const END: u8 = 0xc0;
+const ESC: u8 = 0xdb;
+const ESC_END: u8 = 0xdc;
+const ESC_ESC: u8 = 0xdd;
+
+/// A `Codec` implementation for the Serial Line Internet Protocol, as
+/// described in RFC 1055.
+///
+/// Each frame is followed by an END byte, `0xc0`. END and ESC bytes within a
+/// frame are replaced by ESC ESC_END and ESC ESC_ESC. Empty frames are
+/// skipped when decoding, so a sender can start each frame with an END to
+/// flush any line noise the receiver has buffered, which
+/// [`with_leading_end`](#method.with_leading_end) enables.
+///
+/// A frame with an ESC byte followed by anything other than ESC_END or
+/// ESC_ESC is consumed and returned as an error of kind `InvalidData`, and
+/// the following frames still decode.
+///
+/// # Example
+///
+/// ```
+/// # futures::executor::block_on(async move {
+/// use bytes::Bytes;
+/// use futures::{SinkExt, TryStreamExt};
+/// use futures::io::Cursor;
+/// use futures_codec::{Framed, SlipCodec};
+///
+/// let codec = SlipCodec::new().with_leading_end(true);
+/// let mut framed = Framed::new(Cursor::new(Vec::new()), codec);
+/// framed.send(Bytes::from_static(b"\x01\xc0\x02")).await?;
+///
+/// let (cur, codec) = framed.release();
+/// assert_eq!(cur.get_ref(), b"\xc0\x01\xdb\xdc\x02\xc0");
+///
+/// let mut framed = Framed::new(Cursor::new(cur.into_inner()), codec);
+/// assert_eq!(framed.try_next().await?, Some(Bytes::from_static(b"\x01\xc0\x02")));
+/// # Ok::<_, std::io::Error>(())
+/// # }).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SlipCodec {
+    leading_end: bool,
+    max_frame_length: Option<usize>,
+    discard_oversized: bool,
+    // Skipping the rest of an oversized frame
+    discarding: bool,
+    // Bytes of the buffer already known not to be an END
+    next_index: usize,
+}
+
+impl SlipCodec {
+    /// Creates a new `SlipCodec` without a maximum frame length
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether encoded frames also start with an END byte, defaults to
+    /// `false`
+    pub fn with_leading_end(mut self, leading_end: bool) -> Self {
+        self.leading_end = leading_end;
+        self
+    }
+
+    /// Sets the maximum length of an escaped frame, excluding its END bytes
+    ///
+    /// Decoding a longer frame returns a [`FrameTooLarge`] error, as soon as
+    /// more bytes than this are buffered without an END. Encoding an item
+    /// whose escaped frame would be longer returns the same error.
+    pub fn with_max_frame_length(mut self, max: usize) -> Self {
+        self.max_frame_length = Some(max);
+        self
+    }
+
+    /// Sets whether oversized frames are skipped up to the next END after
+    /// returning the error, so decoding can carry on with the next frame
+    ///
+    /// Otherwise an oversized frame is left in the buffer, and decoding
+    /// returns the same error again.
+    pub fn with_discard_oversized(mut self, discard: bool) -> Self {
+        self.discard_oversized = discard;
+        self
+    }
+}
+
+fn unescape(frame: BytesMut) -> Result<Bytes, Error> {
+    if memchr(ESC, &frame).is_none() {
+        return Ok(frame.freeze());
+    }
+
+    let mut dst = BytesMut::with_capacity(frame.len());
+    let mut bytes = frame.iter();
+    while let Some(&b) = bytes.next() {
+        let b = match b {
+            ESC => match bytes.next() {
+                Some(&ESC_END) => END,
+                Some(&ESC_ESC) => ESC,
+                _ => return Err(Error::new(ErrorKind::InvalidData, "invalid SLIP escape")),
+            },
+            b => b,
+        };
+        dst.put_u8(b);
+    }
+    Ok(dst.freeze())
+}
+
+impl Encoder for SlipCodec {
+    type Item = Bytes;
+    type Error = Error;
+
+    fn encode(&mut self, src: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let start = dst.len();
+        dst.reserve(src.len() + 2);
+        if self.leading_end {
+            dst.put_u8(END);
+        }
+
+        let mut rest = &src[..];
+        while let Some(i) = memchr2(END, ESC, rest) {
+            dst.put_slice(&rest[..i]);
+            dst.put_u8(ESC);
+            dst.put_u8(if rest[i] == END { ESC_END } else { ESC_ESC });
+            rest = &rest[i + 1..];
+        }
+        dst.put_slice(rest);
+
+        if let Some(max) = self.max_frame_length {
+            let len = dst.len() - start - self.leading_end as usize;
+            if len > max {
+                dst.truncate(start);
+                let err = FrameTooLarge { len, max };
+                return Err(Error::new(ErrorKind::InvalidInput, err));
+            }
+        }
+
+        dst.put_u8(END);
+        Ok(())
+    }
+}
+
+impl Decoder for SlipCodec {
+    type Item = Bytes;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            if self.next_index > src.len() {
+                self.next_index = 0;
+            }
+            let end = match memchr(END, &src[self.next_index..]) {
+                Some(i) => self.next_index + i,
+                None => {
+                    self.next_index = src.len();
+                    if self.discarding {
+                        src.clear();
+                        self.next_index = 0;
+                        return Ok(None);
+                    }
+                    return match self.max_frame_length {
+                        Some(max) if src.len() > max => {
+                            let len = src.len();
+                            if self.discard_oversized {
+                                src.clear();
+                                self.next_index = 0;
+                                self.discarding = true;
+                            }
+                            Err(FrameTooLarge { len, max }.into())
+                        }
+                        _ => Ok(None),
+                    };
+                }
+            };
+            self.next_index = 0;
+
+            if self.discarding {
+                src.advance(end + 1);
+                self.discarding = false;
+                continue;
+            }
+            if let Some(max) = self.max_frame_length {
+                if end > max {
+                    if self.discard_oversized {
+                        src.advance(end + 1);
+                    }
+                    return Err(FrameTooLarge { len: end, max }.into());
+                }
+            }
+
+            let mut frame = src.split_to(end + 1);
+            // RFC 1055 suggests dropping empty frames, which a leading END
+            // produces
+            if end == 0 {
+                continue;
+            }
+
+            frame.truncate(end);
+            return unescape(frame).map(Some);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_escapes_and_unescapes() {
+        let mut codec = SlipCodec::new();
+        let mut buf = BytesMut::new();
+        let item = Bytes::from_static(b"\xc0\xdb\xdc\xdd\xc0");
+
+        codec.encode(item.clone(), &mut buf).unwrap();
+        assert_eq!(&buf[..], b"\xdb\xdc\xdb\xdd\xdc\xdd\xdb\xdc\xc0");
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(item));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn it_skips_empty_frames() {
+        let mut codec = SlipCodec::new();
+        let mut buf = BytesMut::from(&b"\xc0\xc0ab\xc0\xc0"[..]);
+
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::from("ab")));
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn it_recovers_from_invalid_escapes() {
+        let mut codec = SlipCodec::new();
+        let mut buf = BytesMut::from(&b"a\xdbb\xc0c\xdb\xc0d\xc0"[..]);
+
+        for _ in 0..2 {
+            let err = codec.decode(&mut buf).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+        }
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::from("d")));
+    }
+
+    #[test]
+    fn it_discards_oversized_frames() {
+        let mut codec = SlipCodec::new()
+            .with_max_frame_length(3)
+            .with_discard_oversized(true);
+        let mut buf = BytesMut::from(&b"abc"[..]);
+
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        buf.extend_from_slice(b"de");
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        buf.extend_from_slice(b"f\xc0g\xc0");
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::from("g")));
+
+        let err = codec
+            .encode(Bytes::from_static(b"a\xc0b"), &mut buf)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn it_keeps_oversized_frames_without_discard() {
+        let mut codec = SlipCodec::new().with_max_frame_length(3);
+        let input = &b"abcd\xc0e\xc0"[..];
+        let mut buf = BytesMut::from(input);
+
+        for _ in 0..2 {
+            let err = codec.decode(&mut buf).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+            assert_eq!(&buf[..], input);
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use super::FrameTooLarge;
+use crate::{Decoder, Encoder};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::cmp;
+use std::io::{Error, ErrorKind};
+
+// A u64 takes at most 10 groups of 7 bits
+const MAX_VARINT_LENGTH: usize = 10;
+
+/// A `Codec` implementation prefixing frames with their length as an
+/// unsigned LEB128 varint, as used by protobuf length-delimited streams.
+///
+/// # Example
+///
+/// ```
+/// # futures::executor::block_on(async move {
+/// use bytes::Bytes;
+/// use futures::{SinkExt, TryStreamExt};
+/// use futures::io::Cursor;
+/// use futures_codec::{Framed, VarintLengthCodec};
+///
+/// let codec = VarintLengthCodec::new().with_max_frame_length(1024);
+/// let mut framed = Framed::new(Cursor::new(Vec::new()), codec);
+/// framed.send(Bytes::from(vec![7u8; 300])).await?;
+///
+/// let (cur, codec) = framed.release();
+/// assert_eq!(&cur.get_ref()[..2], &[0xac, 0x02]);
+///
+/// let mut framed = Framed::new(Cursor::new(cur.into_inner()), codec);
+/// assert_eq!(framed.try_next().await?.map(|b| b.len()), Some(300));
+/// # Ok::<_, std::io::Error>(())
+/// # }).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VarintLengthCodec {
+    max_frame_length: Option<usize>,
+    discard_oversized: bool,
+    // Bytes left to skip from an oversized frame
+    discarding: usize,
+}
+
+impl VarintLengthCodec {
+    /// Creates a new `VarintLengthCodec` without a maximum frame length
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum length of a frame, excluding its header
+    ///
+    /// Decoding a frame with a larger length returns a [`FrameTooLarge`]
+    /// error, as does encoding an item larger than this.
+    pub fn with_max_frame_length(mut self, max: usize) -> Self {
+        self.max_frame_length = Some(max);
+        self
+    }
+
+    /// Sets whether oversized frames are skipped after returning the error,
+    /// so decoding can carry on with the next frame
+    pub fn with_discard_oversized(mut self, discard: bool) -> Self {
+        self.discard_oversized = discard;
+        self
+    }
+}
+
+/// Writes `value` as an unsigned LEB128 varint
+pub(crate) fn encode_varint(mut value: u64, dst: &mut BytesMut) {
+    dst.reserve(MAX_VARINT_LENGTH);
+    while value >= 0x80 {
+        dst.put_u8(value as u8 | 0x80);
+        value >>= 7;
+    }
+    dst.put_u8(value as u8);
+}
+
+/// Reads an unsigned LEB128 varint, returning its value and length in bytes
+///
+/// Returns `None` if `src` ends before the varint does.
+pub(crate) fn decode_varint(src: &[u8]) -> Result<Option<(u64, usize)>, Error> {
+    let mut value = 0u64;
+    for (i, b) in src.iter().take(MAX_VARINT_LENGTH).enumerate() {
+        let bits = u64::from(b & 0x7f);
+        if i == MAX_VARINT_LENGTH - 1 && (b & 0x80 != 0 || bits > 1) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "varint is longer than 10 bytes or overflows a u64",
+            ));
+        }
+        value |= bits << (7 * i);
+        if b & 0x80 == 0 {
+            return Ok(Some((value, i + 1)));
+        }
+    }
+    Ok(None)
+}
+
+impl Encoder for VarintLengthCodec {
+    type Item = Bytes;
+    type Error = Error;
+
+    fn encode(&mut self, src: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        if let Some(max) = self.max_frame_length {
+            if src.len() > max {
+                let err = FrameTooLarge {
+                    len: src.len(),
+                    max,
+                };
+                return Err(Error::new(ErrorKind::InvalidInput, err));
+            }
+        }
+
+        encode_varint(src.len() as u64, dst);
+        dst.extend_from_slice(&src);
+        Ok(())
+    }
+}
+
+impl Decoder for VarintLengthCodec {
+    type Item = Bytes;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if self.discarding > 0 {
+            let n = cmp::min(self.discarding, src.len());
+            src.advance(n);
+            self.discarding -= n;
+            if self.discarding > 0 {
+                return Ok(None);
+            }
+        }
+
+        let (len, header_len) = match decode_varint(src)? {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        if len > (usize::MAX - header_len) as u64 {
+            return Err(Error::new(ErrorKind::InvalidData, "invalid frame length"));
+        }
+        let len = len as usize;
+
+        if let Some(max) = self.max_frame_length {
+            if len > max {
+                if self.discard_oversized {
+                    self.discarding = header_len + len;
+                    let n = cmp::min(self.discarding, src.len());
+                    src.advance(n);
+                    self.discarding -= n;
+                }
+                return Err(FrameTooLarge { len, max }.into());
+            }
+        }
+
+        // The buffer isn't reserved up front, as the length comes from the peer
+        if src.len() < header_len + len {
+            return Ok(None);
+        }
+
+        src.advance(header_len);
+        Ok(Some(src.split_to(len).freeze()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_round_trips_varints() {
+        for value in &[0, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX] {
+            let mut buf = BytesMut::new();
+            encode_varint(*value, &mut buf);
+
+            let decoded = decode_varint(&buf).unwrap();
+            assert_eq!(decoded, Some((*value, buf.len())));
+            assert_eq!(decode_varint(&buf[..buf.len() - 1]).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn it_rejects_overlong_and_overflowing_varints() {
+        let overlong = [0x80u8; 11];
+        let overflowing = [0xffu8, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02];
+
+        assert!(decode_varint(&overlong).is_err());
+        assert!(decode_varint(&overflowing).is_err());
+    }
+
+    #[test]
+    fn it_does_not_allocate_for_announced_lengths() {
+        let mut codec = VarintLengthCodec::new();
+        let mut src = BytesMut::from(&[0xffu8, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f][..]);
+
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+        assert!(src.capacity() < 1024);
+    }
+
+    #[test]
+    fn it_discards_oversized_frames() {
+        let mut codec = VarintLengthCodec::new()
+            .with_max_frame_length(2)
+            .with_discard_oversized(true);
+        let mut src = BytesMut::from(&[3u8, 1, 2, 3, 1, 9][..]);
+
+        let err = codec.decode(&mut src).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            codec.decode(&mut src).unwrap(),
+            Some(Bytes::from(&[9u8][..]))
+        );
+    }
+}
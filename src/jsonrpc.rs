@@ -0,0 +1,514 @@
+//! Typed [JSON-RPC 2.0](https://www.jsonrpc.org/specification) messages.
+//!
+//! The types check the `"jsonrpc": "2.0"` envelope as they are deserialized,
+//! so they can be used with any serde codec. [`JsonRpcCodec`] is the
+//! [`JsonCodec`] for them, which supports both back-to-back and
+//! newline-delimited framing. For Content-Length framing, as used by
+//! language servers, use [`LspCodec`] with [`Packet`] instead.
+//!
+//! ```
+//! # futures::executor::block_on(async move {
+//! use futures::{SinkExt, TryStreamExt};
+//! use futures::io::Cursor;
+//! use futures_codec::Framed;
+//! use futures_codec::jsonrpc::{JsonRpcCodec, Message, Packet, Request, Response};
+//! use serde_json::json;
+//!
+//! let mut framed = Framed::new(Cursor::new(Vec::new()), JsonRpcCodec::new().with_ndjson(true));
+//! let request = Request::new("subtract", Some(json!([42, 23])), 1);
+//! framed.send(Packet::Single(request.into())).await.unwrap();
+//!
+//! let (io, codec) = framed.release();
+//! let mut framed = Framed::new(Cursor::new(io.into_inner()), codec);
+//! match framed.try_next().await.unwrap() {
+//!     Some(Packet::Single(Message::Request(request))) => {
+//!         let response = Response::success(request.id, json!(19));
+//!         assert_eq!(response.result, Ok(json!(19)));
+//!     }
+//!     other => panic!("unexpected packet: {:?}", other),
+//! }
+//! # });
+//! ```
+//!
+//! [`JsonCodec`]: ../struct.JsonCodec.html
+//! [`JsonRpcCodec`]: type.JsonRpcCodec.html
+//! [`LspCodec`]: ../struct.LspCodec.html
+//! [`Packet`]: enum.Packet.html
+
+use crate::JsonCodec;
+use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
+use serde::de::{self, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::Value;
+use std::fmt;
+
+/// A `JsonCodec` for JSON-RPC 2.0 packets
+///
+/// Packets which are not valid JSON-RPC 2.0 fail to decode with
+/// `JsonCodecError::Json`.
+pub type JsonRpcCodec = JsonCodec<Packet, Packet>;
+
+/// The id of a request, echoed back in its response
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Id {
+    /// A numeric id
+    Number(i64),
+    /// A string id
+    String(String),
+    /// A null id, used in responses to requests whose id could not be read
+    Null,
+}
+
+impl From<i64> for Id {
+    fn from(id: i64) -> Self {
+        Id::Number(id)
+    }
+}
+
+impl From<String> for Id {
+    fn from(id: String) -> Self {
+        Id::String(id)
+    }
+}
+
+impl From<&str> for Id {
+    fn from(id: &str) -> Self {
+        Id::String(id.to_owned())
+    }
+}
+
+/// A request which expects a response
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    /// Name of the method to invoke
+    pub method: String,
+    /// Parameters of the call, an array or an object
+    pub params: Option<Value>,
+    /// Id of the request
+    pub id: Id,
+}
+
+impl Request {
+    /// Creates a new request
+    pub fn new(method: impl Into<String>, params: Option<Value>, id: impl Into<Id>) -> Self {
+        Request {
+            method: method.into(),
+            params,
+            id: id.into(),
+        }
+    }
+}
+
+/// A request without an id, which gets no response
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    /// Name of the method to invoke
+    pub method: String,
+    /// Parameters of the call, an array or an object
+    pub params: Option<Value>,
+}
+
+impl Notification {
+    /// Creates a new notification
+    pub fn new(method: impl Into<String>, params: Option<Value>) -> Self {
+        Notification {
+            method: method.into(),
+            params,
+        }
+    }
+}
+
+/// The response to a request
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    /// Id of the request
+    pub id: Id,
+    /// Either the result of the call or the error it failed with
+    pub result: Result<Value, ErrorObject>,
+}
+
+impl Response {
+    /// Creates a successful response
+    pub fn success(id: impl Into<Id>, result: Value) -> Self {
+        Response {
+            id: id.into(),
+            result: Ok(result),
+        }
+    }
+
+    /// Creates an error response
+    pub fn error(id: impl Into<Id>, error: ErrorObject) -> Self {
+        Response {
+            id: id.into(),
+            result: Err(error),
+        }
+    }
+}
+
+/// The error member of a failed response
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorObject {
+    /// Error code, see the constants for those defined by the specification
+    pub code: i64,
+    /// Short description of the error
+    pub message: String,
+    /// Additional information about the error
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl ErrorObject {
+    /// Invalid JSON was received
+    pub const PARSE_ERROR: i64 = -32700;
+    /// The JSON sent is not a valid request
+    pub const INVALID_REQUEST: i64 = -32600;
+    /// The method does not exist or is not available
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    /// Invalid method parameters
+    pub const INVALID_PARAMS: i64 = -32602;
+    /// Internal JSON-RPC error
+    pub const INTERNAL_ERROR: i64 = -32603;
+
+    /// Creates a new error without data
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        ErrorObject {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+impl fmt::Display for ErrorObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "JSON-RPC error {}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for ErrorObject {}
+
+/// Any JSON-RPC 2.0 message
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// A request
+    Request(Request),
+    /// A notification
+    Notification(Notification),
+    /// A response
+    Response(Response),
+}
+
+impl From<Request> for Message {
+    fn from(request: Request) -> Self {
+        Message::Request(request)
+    }
+}
+
+impl From<Notification> for Message {
+    fn from(notification: Notification) -> Self {
+        Message::Notification(notification)
+    }
+}
+
+impl From<Response> for Message {
+    fn from(response: Response) -> Self {
+        Message::Response(response)
+    }
+}
+
+/// What is sent in one go: a single message or a batch of them
+#[derive(Debug, Clone, PartialEq)]
+pub enum Packet {
+    /// A single message
+    Single(Message),
+    /// A batch of messages, which may not be empty
+    Batch(Vec<Message>),
+}
+
+impl From<Message> for Packet {
+    fn from(message: Message) -> Self {
+        Packet::Single(message)
+    }
+}
+
+// The "jsonrpc" member, which must be exactly "2.0"
+#[derive(Debug, Clone, Copy)]
+struct Version;
+
+impl Serialize for Version {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str("2.0")
+    }
+}
+
+impl<'de> Deserialize<'de> for Version {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let version = String::deserialize(deserializer)?;
+        if version == "2.0" {
+            Ok(Version)
+        } else {
+            Err(de::Error::invalid_value(
+                de::Unexpected::Str(&version),
+                &"\"2.0\"",
+            ))
+        }
+    }
+}
+
+// The members of any message, as they are written
+#[derive(Serialize)]
+struct EnvelopeRef<'a> {
+    jsonrpc: Version,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    method: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    params: Option<&'a Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<&'a Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a ErrorObject>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<&'a Id>,
+}
+
+// The members of any message, as they are read. Members which may be null
+// are wrapped in an `Option` telling whether they are present at all.
+#[derive(Deserialize)]
+struct Envelope {
+    // Only read to check it
+    #[serde(rename = "jsonrpc")]
+    _jsonrpc: Version,
+    #[serde(default)]
+    method: Option<String>,
+    #[serde(default, deserialize_with = "present")]
+    params: Option<Value>,
+    #[serde(default, deserialize_with = "present")]
+    result: Option<Value>,
+    #[serde(default)]
+    error: Option<ErrorObject>,
+    #[serde(default, deserialize_with = "present")]
+    id: Option<Id>,
+}
+
+fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+impl Envelope {
+    fn into_message<E: de::Error>(self) -> Result<Message, E> {
+        if let Some(params) = &self.params {
+            if !params.is_array() && !params.is_object() {
+                return Err(E::custom("params must be an array or an object"));
+            }
+        }
+
+        match (self.method, self.result, self.error) {
+            (Some(method), None, None) => Ok(match self.id {
+                Some(id) => Message::Request(Request {
+                    method,
+                    params: self.params,
+                    id,
+                }),
+                None => Message::Notification(Notification {
+                    method,
+                    params: self.params,
+                }),
+            }),
+            (None, result, error) if self.params.is_none() => {
+                let id = self.id.ok_or_else(|| E::missing_field("id"))?;
+                let result = match (result, error) {
+                    (Some(result), None) => Ok(result),
+                    (None, Some(error)) => Err(error),
+                    _ => {
+                        return Err(E::custom(
+                            "a response must have exactly one of result or error",
+                        ))
+                    }
+                };
+                Ok(Message::Response(Response { id, result }))
+            }
+            _ => Err(E::custom("not a request, notification or response")),
+        }
+    }
+}
+
+impl<'a> EnvelopeRef<'a> {
+    fn new(method: Option<&'a str>, params: Option<&'a Value>, id: Option<&'a Id>) -> Self {
+        EnvelopeRef {
+            jsonrpc: Version,
+            method,
+            params,
+            result: None,
+            error: None,
+            id,
+        }
+    }
+}
+
+impl Serialize for Request {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        EnvelopeRef::new(Some(&self.method), self.params.as_ref(), Some(&self.id))
+            .serialize(serializer)
+    }
+}
+
+impl Serialize for Notification {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        EnvelopeRef::new(Some(&self.method), self.params.as_ref(), None).serialize(serializer)
+    }
+}
+
+impl Serialize for Response {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut envelope = EnvelopeRef::new(None, None, Some(&self.id));
+        match &self.result {
+            Ok(result) => envelope.result = Some(result),
+            Err(error) => envelope.error = Some(error),
+        }
+        envelope.serialize(serializer)
+    }
+}
+
+impl Serialize for Message {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Message::Request(request) => request.serialize(serializer),
+            Message::Notification(notification) => notification.serialize(serializer),
+            Message::Response(response) => response.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Message {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Envelope::deserialize(deserializer)?.into_message()
+    }
+}
+
+// Reads a message of one kind, rejecting the others
+macro_rules! deserialize_message {
+    ($ty:ident, $expecting:expr) => {
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                match Message::deserialize(deserializer)? {
+                    Message::$ty(message) => Ok(message),
+                    _ => Err(de::Error::custom(concat!("expected ", $expecting))),
+                }
+            }
+        }
+    };
+}
+
+deserialize_message!(Request, "a request");
+deserialize_message!(Notification, "a notification");
+deserialize_message!(Response, "a response");
+
+impl Serialize for Packet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Packet::Single(message) => message.serialize(serializer),
+            Packet::Batch(messages) => messages.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Packet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct PacketVisitor;
+
+        impl<'de> Visitor<'de> for PacketVisitor {
+            type Value = Packet;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a JSON-RPC message or a batch of them")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Packet, A::Error> {
+                Message::deserialize(MapAccessDeserializer::new(map)).map(Packet::Single)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Packet, A::Error> {
+                let messages = Vec::<Message>::deserialize(SeqAccessDeserializer::new(seq))?;
+                if messages.is_empty() {
+                    return Err(de::Error::invalid_length(0, &"a non-empty batch"));
+                }
+                Ok(Packet::Batch(messages))
+            }
+        }
+
+        deserializer.deserialize_any(PacketVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(s: &str) -> serde_json::Result<Packet> {
+        serde_json::from_str(s)
+    }
+
+    #[test]
+    fn it_round_trips_messages() {
+        let messages: Vec<Message> = vec![
+            Request::new("subtract", Some(json!({"a": 1})), "abc").into(),
+            Request::new("ping", None, Id::Null).into(),
+            Notification::new("update", Some(json!([1, 2]))).into(),
+            Response::success(1, Value::Null).into(),
+            Response::error(2, ErrorObject::new(ErrorObject::METHOD_NOT_FOUND, "nope")).into(),
+        ];
+        let packet = Packet::Batch(messages);
+
+        let s = serde_json::to_string(&packet).unwrap();
+        assert_eq!(parse(&s).unwrap(), packet);
+    }
+
+    #[test]
+    fn it_writes_the_envelope() {
+        let response = Response::success(7, json!("ok"));
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            json!({"jsonrpc": "2.0", "result": "ok", "id": 7})
+        );
+        let notification = Notification::new("tick", None);
+        assert_eq!(
+            serde_json::to_value(&notification).unwrap(),
+            json!({"jsonrpc": "2.0", "method": "tick"})
+        );
+    }
+
+    #[test]
+    fn it_rejects_invalid_envelopes() {
+        for s in &[
+            r#"{"jsonrpc": "1.0", "method": "a", "id": 1}"#,
+            r#"{"method": "a", "id": 1}"#,
+            r#"{"jsonrpc": "2.0", "method": "a", "params": 3}"#,
+            r#"{"jsonrpc": "2.0", "result": 1, "error": {"code": 1, "message": ""}, "id": 1}"#,
+            r#"{"jsonrpc": "2.0", "result": 1}"#,
+            r#"{"jsonrpc": "2.0", "method": "a", "result": 1, "id": 1}"#,
+            r#"{"jsonrpc": "2.0", "id": 1}"#,
+            r#"[]"#,
+            r#"3"#,
+        ] {
+            assert!(parse(s).is_err(), "accepted {}", s);
+        }
+    }
+
+    #[test]
+    fn it_tells_null_results_and_ids_apart() {
+        let response: Response =
+            serde_json::from_str(r#"{"jsonrpc": "2.0", "result": null, "id": null}"#).unwrap();
+        assert_eq!(response, Response::success(Id::Null, Value::Null));
+
+        let request: Request =
+            serde_json::from_str(r#"{"jsonrpc": "2.0", "method": "a", "id": null}"#).unwrap();
+        assert_eq!(request.id, Id::Null);
+        assert!(serde_json::from_str::<Request>(r#"{"jsonrpc": "2.0", "method": "a"}"#).is_err());
+    }
+}
@@ -12,7 +12,7 @@
 //! use futures_codec::{LinesCodec, Framed};
 //!
 //! let io = Cursor::new(Vec::new());
-//! let mut framed = Framed::new(io, LinesCodec);
+//! let mut framed = Framed::new(io, LinesCodec::new());
 //!
 //! while let Some(line) = framed.try_next().await? {
 //!     dbg!(line);
@@ -23,12 +23,32 @@
 
 mod codec;
 pub use bytes::{Bytes, BytesMut};
-pub use codec::{BytesCodec, LengthCodec, LinesCodec};
+pub use codec::{
+    AnyDelimiterCodec, BytesCodec, BytesLinesCodec, CobsCodec, FrameTooLarge, LengthCodec,
+    LengthDelimitedCodec, LengthDelimitedCodecBuilder, LinesCodec, SlipCodec, VarintLengthCodec,
+};
 
+#[cfg(feature = "bincode")]
+pub use codec::{BincodeCodec, BincodeCodecError};
 #[cfg(feature = "cbor")]
-pub use codec::{CborCodec, CborCodecError};
+pub use codec::{CborCodec, CborCodecError, RawCborCodec};
+#[cfg(any(feature = "crc32", feature = "crc32c", feature = "xxhash"))]
+pub use codec::{ChecksumAlgorithm, Checksummed, ChecksummedError};
+#[cfg(any(feature = "deflate", feature = "zstd", feature = "lz4"))]
+pub use codec::{Compressed, CompressedError, CompressionAlgorithm};
 #[cfg(feature = "json")]
-pub use codec::{JsonCodec, JsonCodecError};
+pub use codec::{JsonCodec, JsonCodecError, JsonSeqCodec, LspCodec, LspCodecError, RawJsonCodec};
+#[cfg(feature = "msgpack")]
+pub use codec::{MsgPackCodec, MsgPackCodecError};
+#[cfg(feature = "postcard")]
+pub use codec::{PostcardCodec, PostcardCodecError};
+#[cfg(feature = "prost")]
+pub use codec::{ProtobufCodec, ProtobufCodecError};
+
+#[cfg(feature = "jsonrpc")]
+pub mod jsonrpc;
+#[cfg(feature = "jsonrpc")]
+pub use jsonrpc::JsonRpcCodec;
 
 mod decoder;
 pub use decoder::Decoder;
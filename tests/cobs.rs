@@ -0,0 +1,41 @@
+use futures::io::Cursor;
+use futures::{executor, SinkExt, StreamExt, TryStreamExt};
+use futures_codec::{Bytes, CobsCodec, Framed, FramedRead};
+use std::io;
+
+#[test]
+fn same_msgs_are_received_as_were_sent() {
+    let cur = Cursor::new(Vec::new());
+    let mut framed = Framed::new(cur, CobsCodec::new());
+
+    let msgs = vec![
+        Bytes::from("msg1"),
+        Bytes::from(vec![0u8; 10]),
+        Bytes::from((0..1000).map(|i| i as u8).collect::<Vec<_>>()),
+    ];
+    for msg in msgs.clone() {
+        executor::block_on(framed.send(msg)).unwrap();
+    }
+
+    let (mut cur, codec) = framed.release();
+    assert_eq!(cur.get_ref().iter().filter(|b| **b == 0).count(), 3);
+    cur.set_position(0);
+    let framed = Framed::new(cur, codec);
+
+    let recv_msgs = framed.map(|res| res.unwrap()).collect::<Vec<_>>();
+    assert_eq!(executor::block_on(recv_msgs), msgs);
+}
+
+#[test]
+fn decoding_resyncs_after_line_noise() {
+    // A frame missing its last byte, followed by a good one
+    let input = b"\x04ab\x00\x03cd\x00";
+    let mut framed = FramedRead::new(&input[..], CobsCodec::new());
+
+    let err = executor::block_on(framed.try_next()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    let frame = executor::block_on(framed.try_next()).unwrap();
+    assert_eq!(frame, Some(Bytes::from("cd")));
+    assert!(executor::block_on(framed.try_next()).unwrap().is_none());
+}
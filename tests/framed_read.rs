@@ -30,7 +30,7 @@ impl AsyncRead for MockBurstySender {
 #[test]
 fn line_read_multi() {
     let io = MockBurstySender { sent: false };
-    let mut framed = FramedRead::new(io, LinesCodec {});
+    let mut framed = FramedRead::new(io, LinesCodec::new());
     let one = executor::block_on(framed.next()).unwrap().unwrap();
     assert_eq!(one, "one\n");
     let two = executor::block_on(framed.next()).unwrap().unwrap();
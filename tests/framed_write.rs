@@ -54,7 +54,7 @@ impl AsyncWrite for AsyncWriteNull {
 #[test]
 fn line_write() {
     let curs = Cursor::new(vec![0u8; 16]);
-    let mut framer = FramedWrite::new(curs, LinesCodec {});
+    let mut framer = FramedWrite::new(curs, LinesCodec::new());
     executor::block_on(framer.send("Hello\n".to_owned())).unwrap();
     executor::block_on(framer.send("World\n".to_owned())).unwrap();
     let (curs, _) = framer.release();
@@ -66,7 +66,7 @@ fn line_write() {
 fn line_write_to_eof() {
     let mut buf = [0u8; 16];
     let curs = Cursor::new(&mut buf[..]);
-    let mut framer = FramedWrite::new(curs, LinesCodec {});
+    let mut framer = FramedWrite::new(curs, LinesCodec::new());
     let _err =
         executor::block_on(framer.send("This will fill up the buffer\n".to_owned())).unwrap_err();
     let (curs, _) = framer.release();
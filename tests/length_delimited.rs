@@ -1,11 +1,11 @@
 use futures::io::Cursor;
 use futures::{executor, SinkExt, StreamExt};
-use futures_codec::{Bytes, Framed, LengthCodec};
+use futures_codec::{Bytes, Framed, LengthCodec, LengthDelimitedCodec};
 
 #[test]
 fn same_msgs_are_received_as_were_sent() {
     let cur = Cursor::new(vec![0; 256]);
-    let mut framed = Framed::new(cur, LengthCodec {});
+    let mut framed = Framed::new(cur, LengthCodec::new());
 
     let send_msgs = async {
         framed.send(Bytes::from("msg1")).await.unwrap();
@@ -16,7 +16,7 @@ fn same_msgs_are_received_as_were_sent() {
 
     let (mut cur, _) = framed.release();
     cur.set_position(0);
-    let framed = Framed::new(cur, LengthCodec {});
+    let framed = Framed::new(cur, LengthCodec::new());
 
     let recv_msgs = framed
         .take(3)
@@ -27,3 +27,26 @@ fn same_msgs_are_received_as_were_sent() {
 
     assert!(msgs == vec!["msg1", "msg2", "msg3"]);
 }
+
+#[test]
+fn builder_configures_header_layout() {
+    let codec = LengthDelimitedCodec::builder()
+        .length_field_length(2)
+        .little_endian()
+        .new_codec();
+    let cur = Cursor::new(vec![0; 32]);
+    let mut framed = Framed::new(cur, codec);
+
+    executor::block_on(framed.send(Bytes::from("msg1"))).unwrap();
+    executor::block_on(framed.send(Bytes::from("msg22"))).unwrap();
+
+    let (mut cur, codec) = framed.release();
+    assert_eq!(&cur.get_ref()[..13], b"\x04\x00msg1\x05\x00msg22");
+    cur.set_position(0);
+    let framed = Framed::new(cur, codec);
+
+    let recv_msgs = framed.take(2).map(|res| res.unwrap()).collect::<Vec<_>>();
+    let msgs = executor::block_on(recv_msgs);
+
+    assert_eq!(msgs, vec![Bytes::from("msg1"), Bytes::from("msg22")]);
+}
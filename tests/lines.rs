@@ -7,11 +7,26 @@ fn it_works() {
     let buf = "Hello\nWorld\nError".to_owned();
     let cur = Cursor::new(buf);
 
-    let mut framed = FramedRead::new(cur, LinesCodec {});
+    let mut framed = FramedRead::new(cur, LinesCodec::new());
     let next = executor::block_on(framed.try_next()).unwrap().unwrap();
     assert_eq!(next, "Hello\n");
     let next = executor::block_on(framed.try_next()).unwrap().unwrap();
     assert_eq!(next, "World\n");
+    let next = executor::block_on(framed.try_next()).unwrap().unwrap();
+    assert_eq!(next, "Error");
+
+    assert!(executor::block_on(framed.try_next()).unwrap().is_none());
+}
+
+#[test]
+fn it_requires_a_terminator() {
+    let buf = "Hello\nError".to_owned();
+    let cur = Cursor::new(buf);
+
+    let codec = LinesCodec::new().with_require_terminator(true);
+    let mut framed = FramedRead::new(cur, codec);
+    let next = executor::block_on(framed.try_next()).unwrap().unwrap();
+    assert_eq!(next, "Hello\n");
 
     assert!(executor::block_on(framed.try_next()).is_err());
 }
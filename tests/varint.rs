@@ -0,0 +1,65 @@
+use futures::io::{AsyncRead, Cursor};
+use futures::{executor, SinkExt, StreamExt, TryStreamExt};
+use futures_codec::{Bytes, Framed, FramedRead, VarintLengthCodec};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+struct OneByteAtATime<'a> {
+    input: &'a [u8],
+}
+impl AsyncRead for OneByteAtATime<'_> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.input.is_empty() {
+            Poll::Ready(Ok(0))
+        } else {
+            buf[0] = self.input[0];
+            self.input = &self.input[1..];
+            Poll::Ready(Ok(1))
+        }
+    }
+}
+
+#[test]
+fn same_msgs_are_received_as_were_sent() {
+    let cur = Cursor::new(Vec::new());
+    let mut framed = Framed::new(cur, VarintLengthCodec::new());
+
+    let msgs = vec![Bytes::from("msg1"), Bytes::from(vec![1u8; 1000])];
+    for msg in msgs.clone() {
+        executor::block_on(framed.send(msg)).unwrap();
+    }
+
+    let (mut cur, codec) = framed.release();
+    cur.set_position(0);
+    let framed = Framed::new(cur, codec);
+
+    let recv_msgs = framed.map(|res| res.unwrap()).collect::<Vec<_>>();
+    assert_eq!(executor::block_on(recv_msgs), msgs);
+}
+
+#[test]
+fn header_split_across_reads() {
+    // Two byte varint header for a 200 byte frame
+    let mut input = vec![0xc8, 0x01];
+    input.extend_from_slice(&[5u8; 200]);
+    let mut framed = FramedRead::new(OneByteAtATime { input: &input }, VarintLengthCodec::new());
+
+    let frame = executor::block_on(framed.try_next()).unwrap().unwrap();
+    assert_eq!(frame, Bytes::from(vec![5u8; 200]));
+    assert!(executor::block_on(framed.try_next()).unwrap().is_none());
+}
+
+#[test]
+fn frames_over_the_maximum_are_rejected() {
+    let input = [0xc8u8, 0x01, 0, 0];
+    let codec = VarintLengthCodec::new().with_max_frame_length(100);
+    let mut framed = FramedRead::new(&input[..], codec);
+
+    let err = executor::block_on(framed.try_next()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}